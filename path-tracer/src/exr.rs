@@ -0,0 +1,105 @@
+use path_tracing::Color;
+use std::io::Write;
+
+/// Writes linear colors, row by row from the top, as an uncompressed OpenEXR image with
+/// 32-bit float channels, so values above 1 survive for grading and compositing later.
+pub fn save_exr(path: &str, width: u32, height: u32, colors: &[Color]) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    file.write_all(&encode(width, height, colors))?;
+    file.flush()
+}
+
+fn encode(width: u32, height: u32, colors: &[Color]) -> Vec<u8> {
+    let mut out = vec![0x76, 0x2f, 0x31, 0x01];
+    // version 2, a single part of scanlines
+    out.extend_from_slice(&2u32.to_le_bytes());
+
+    // channels are stored in alphabetical order, each as 32-bit floats sampled at every pixel
+    let mut channels = vec![];
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.push(0);
+        channels.extend_from_slice(&2i32.to_le_bytes());
+        channels.extend_from_slice(&[0, 0, 0, 0]);
+        channels.extend_from_slice(&1i32.to_le_bytes());
+        channels.extend_from_slice(&1i32.to_le_bytes());
+    }
+    channels.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|n| n.to_le_bytes())
+        .collect();
+    attribute(&mut out, "channels", "chlist", &channels);
+    attribute(&mut out, "compression", "compression", &[0]);
+    attribute(&mut out, "dataWindow", "box2i", &window);
+    attribute(&mut out, "displayWindow", "box2i", &window);
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut out, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut out, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    out.push(0);
+
+    // a table of where each scanline starts, then the scanlines with their channels one after another
+    let line_size = width as usize * 3 * 4;
+    let table_end = out.len() + height as usize * 8;
+    for y in 0..height as usize {
+        let offset = table_end + y * (8 + line_size);
+        out.extend_from_slice(&(offset as u64).to_le_bytes());
+    }
+    for (y, row) in colors.chunks(width as usize).enumerate() {
+        out.extend_from_slice(&(y as i32).to_le_bytes());
+        out.extend_from_slice(&(line_size as i32).to_le_bytes());
+        for channel in [2, 1, 0] {
+            for color in row {
+                out.extend_from_slice(&(color.channels()[channel] as f32).to_le_bytes());
+            }
+        }
+    }
+    out
+}
+
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    for text in [name, kind] {
+        out.extend_from_slice(text.as_bytes());
+        out.push(0);
+    }
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    #[test]
+    fn scanlines_follow_the_offset_table() {
+        let colors = [
+            Color::new(0.0, 0.5, 1.0),
+            Color::new(2.0, 0.0, 0.0),
+            Color::new(0.25, 0.0, 0.0),
+            Color::black(),
+        ];
+        let bytes = encode(2, 2, &colors);
+        assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+        let read_u64 = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let read_f32 = |at: usize| f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let header_end = bytes.len() - 2 * (8 + 2 * 3 * 4) - 2 * 8;
+        assert_eq!(bytes[header_end - 1], 0);
+
+        let first = read_u64(header_end) as usize;
+        let second = read_u64(header_end + 8) as usize;
+        assert_eq!(first, header_end + 16);
+        assert_eq!(second, first + 8 + 24);
+        // blue of both pixels, then green, then red
+        assert_eq!(read_f32(first + 8), 1.0);
+        assert_eq!(read_f32(first + 8 + 8), 0.5);
+        assert_eq!(read_f32(first + 8 + 20), 2.0);
+        assert_eq!(
+            i32::from_le_bytes(bytes[second..second + 4].try_into().unwrap()),
+            1
+        );
+        assert_eq!(read_f32(second + 8 + 16), 0.25);
+        assert_eq!(bytes.len(), second + 8 + 24);
+    }
+}
@@ -1,59 +1,676 @@
 extern crate image;
 
+mod exr;
+mod options;
+
 use image::RgbImage;
-use path_tracing::{trace_scene, Camera, Dielectric, Lambertian, Metallic, Sphere, Vec3, World};
+use options::{Options, Projection, USAGE};
+use path_tracing::{
+    Animation, Aperture, ApertureMask, Camera, CancellationToken, Checkpoint, Color, Coordinator,
+    CubeMapCamera, Dielectric, EquirectangularCamera, Filter, FisheyeCamera, Hitable, Keyframed,
+    Lambertian, Lens, Metallic, OrthographicCamera, PerspectiveCamera, Point3, ProgressiveRenderer,
+    RealisticCamera, RenderStats, Scattering, Sphere, StereoCamera, StereoLayout, Tile, Transform,
+    Transformed, Vec3, WorkerConnection, World, FULL_FRAME_SENSOR_HEIGHT,
+};
 use rand::prelude::*;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 fn main() -> std::io::Result<()> {
-    let nx = 900;
-    let ny = 600;
-    let ns = 100;
+    let options = Options::parse(std::env::args().skip(1)).map_err(|message| {
+        eprintln!("{}", USAGE);
+        Error::new(ErrorKind::InvalidInput, message)
+    })?;
+    if let Some(address) = &options.worker {
+        return run_worker(address, options.threads);
+    }
 
-    let look_from = Vec3::new(13.0, 2.0, 3.0);
-    let look_at = Vec3::new(0.0, 0.0, 0.0);
-    let dist_to_focus = 10.0;
-    let aperture = 0.1;
+    let filter = options.filter().map_err(invalid_input)?;
+    let animation = match &options.animation {
+        Some(path) => Some(load_animation(path)?),
+        None => None,
+    };
 
-    let camera = Camera::new(
-        look_from,
-        look_at,
-        Vec3::new(0.0, 1.0, 0.0),
-        20.0,
-        nx as f64 / ny as f64,
-        aperture,
-        dist_to_focus,
-    );
+    let nx = options.width;
+    let ny = options.height;
+    let ns = options.samples;
+
+    let resumed = match &options.resume {
+        Some(path) => Some(Checkpoint::load(path)?),
+        None => None,
+    };
+    // the scene is generated from the seed too, so a resumed render uses the checkpoint's
+    let seed = resumed
+        .as_ref()
+        .map_or(options.seed, |checkpoint| checkpoint.seed);
+
+    let scene_start = Instant::now();
+    let mut world = build_book_scene(seed, animation.as_ref());
+    for light in options.lights().map_err(invalid_input)? {
+        world.add_light(light);
+    }
+    let scene_time = scene_start.elapsed();
+
+    let cancellation = CancellationToken::new();
+    let stop = cancellation.clone();
+    ctrlc::set_handler(move || {
+        if stop.is_cancelled() {
+            // a second Ctrl-C stops without waiting for the partial image
+            std::process::exit(130);
+        }
+        eprintln!("\rStopping, press Ctrl-C again to quit immediately");
+        stop.cancel();
+    })
+    .map_err(Error::other)?;
 
+    if let Some(frames) = options.frames {
+        return render_frames(
+            &options,
+            &world,
+            animation.as_ref(),
+            &*filter,
+            frames,
+            &cancellation,
+            scene_time,
+        );
+    }
+    let camera = build_camera(&options, &world, animation.as_ref(), 0.0)?;
     println!("Camera settings: {:?}", camera);
+    let mut renderer = ProgressiveRenderer::new(&world, &*camera, ny, nx, &*filter);
+    renderer.set_seed(seed);
+    if let Some(exposure) = &options.exposure {
+        renderer.set_exposure(exposure.multiplier());
+    }
+    renderer.set_tiles(options.tile_size, options.tile_order);
+    if let Some(region) = options.region {
+        renderer.set_region(region).map_err(invalid_input)?;
+    }
+
+    renderer.set_cancellation_token(cancellation.clone());
+
+    if let Some(checkpoint) = resumed {
+        renderer.resume(checkpoint)?;
+        println!(
+            "Resuming after {} passes: {} samples per pixel",
+            renderer.passes(),
+            renderer.samples_per_pixel()
+        );
+    }
+
+    let mut checkpoints = Checkpoints {
+        path: options
+            .checkpoint
+            .clone()
+            .or_else(|| options.resume.clone()),
+        interval: options.checkpoint_interval,
+        last_saved: Instant::now(),
+    };
+
+    let render_start = Instant::now();
+    let mut output_time = Duration::from_secs(0);
+    if let Some(address) = &options.coordinator {
+        let coordinator = Coordinator::bind(address, &options.scene_description(seed))?;
+        println!("Waiting for workers on {}", coordinator.local_addr()?);
+        let pass_samples = options.pass_samples.unwrap_or(ns);
+        let mut passes = vec![];
+        let mut planned = renderer.samples_per_pixel();
+        while planned < ns {
+            passes.push(u32::min(pass_samples, ns - planned));
+            planned += passes[passes.len() - 1];
+        }
+        // a cancelled render is reported below, along with the passes that did finish
+        if coordinator.render(&renderer, &passes)? {
+            println!(
+                "Rendered {} passes: {} samples per pixel",
+                renderer.passes(),
+                renderer.samples_per_pixel()
+            );
+        }
+        timed(&mut output_time, || {
+            save_render(&options, &options.output, &renderer)
+        })?;
+    } else if let Some(budget) = options.time {
+        let pass_samples = options.pass_samples.unwrap_or(1);
+        let mut saved = Ok(());
+        let deadline = Instant::now()
+            .checked_add(budget)
+            .ok_or_else(|| invalid_input(format!("--time {:?} is too long", budget)))?;
+        renderer.render_until(deadline, pass_samples, |renderer| {
+            if saved.is_ok() {
+                saved = checkpoints.after_pass(renderer);
+            }
+        });
+        saved?;
+        println!(
+            "Rendered {} passes: {} samples per pixel",
+            renderer.passes(),
+            renderer.samples_per_pixel()
+        );
+        timed(&mut output_time, || {
+            save_render(&options, &options.output, &renderer)
+        })?;
+    } else {
+        let pass_samples = options.pass_samples.unwrap_or(ns);
+        while renderer.samples_per_pixel() < ns {
+            let samples = u32::min(pass_samples, ns - renderer.samples_per_pixel());
+            let done = AtomicU32::new(0);
+            // tiles include the border sampled around a region for the filter
+            let region_area: u32 = renderer.tiles().iter().map(Tile::area).sum();
+            let completed = renderer.render_pass_with_progress(samples, |tile, _| {
+                let done = done.fetch_add(tile.area(), Ordering::Relaxed) + tile.area();
+                eprint!("\r{:5.1}%", 100.0 * done as f64 / region_area as f64);
+            });
+            eprint!("\r");
+            if !completed {
+                timed(&mut output_time, || {
+                    save_render(&options, &options.output, &renderer)
+                })?;
+                break;
+            }
+            println!(
+                "Pass {}: {} samples per pixel",
+                renderer.passes(),
+                renderer.samples_per_pixel()
+            );
+            timed(&mut output_time, || {
+                save_render(&options, &options.output, &renderer)
+            })?;
+            checkpoints.after_pass(&renderer)?;
+        }
+    }
+
+    if renderer.is_cancelled() {
+        println!(
+            "Cancelled after {} complete passes, saved the partial image",
+            renderer.passes()
+        );
+    }
+
+    checkpoints.save(&renderer)?;
+    let render_time = render_start.elapsed() - output_time;
+    report_stats(
+        &options,
+        &[
+            ("scene", scene_time),
+            ("render", render_time),
+            ("output", output_time),
+        ],
+    )
+}
+
+/// Runs f, adding the time it took to elapsed.
+fn timed<T, F: FnOnce() -> T>(elapsed: &mut Duration, f: F) -> T {
+    let start = Instant::now();
+    let result = f();
+    *elapsed += start.elapsed();
+    result
+}
+
+/// Renders each frame of an animation to its own file. The scene is shared by every frame;
+/// only the camera is rebuilt, with its shutter opening at the frame's time so keyframed
+/// objects are in their place for that frame.
+/// Cancelling stops the frame being rendered and saves what it has so far.
+fn render_frames(
+    options: &Options,
+    world: &World,
+    animation: Option<&Animation>,
+    filter: &(dyn Filter + Sync),
+    (first, last): (u32, u32),
+    cancellation: &CancellationToken,
+    scene_time: Duration,
+) -> std::io::Result<()> {
+    let render_start = Instant::now();
+    let mut output_time = Duration::from_secs(0);
+    for frame in first..=last {
+        if cancellation.is_cancelled() {
+            break;
+        }
+        let time = frame as f64 / options.fps;
+        let camera = build_camera(options, world, animation, time)?;
+        let mut renderer =
+            ProgressiveRenderer::new(world, &*camera, options.height, options.width, filter);
+        renderer.set_seed(options.seed);
+        renderer.set_cancellation_token(cancellation.clone());
+        if let Some(exposure) = &options.exposure {
+            renderer.set_exposure(exposure.multiplier());
+        }
+        renderer.set_tiles(options.tile_size, options.tile_order);
+        if let Some(region) = options.region {
+            renderer.set_region(region).map_err(invalid_input)?;
+        }
+        let done = AtomicU32::new(0);
+        let region_area: u32 = renderer.tiles().iter().map(Tile::area).sum();
+        let completed = renderer.render_pass_with_progress(options.samples, |tile, _| {
+            let done = done.fetch_add(tile.area(), Ordering::Relaxed) + tile.area();
+            eprint!("\r{:5.1}%", 100.0 * done as f64 / region_area as f64);
+        });
+        eprint!("\r");
+        let output = options.frame_output(frame);
+        timed(&mut output_time, || {
+            save_render(options, &output, &renderer)
+        })?;
+        if !completed {
+            println!(
+                "Cancelled during frame {}, saved its partial image to {}",
+                frame, output
+            );
+            break;
+        }
+        println!("Frame {} at {:.3}s: {}", frame, time, output);
+    }
+    let render_time = render_start.elapsed() - output_time;
+    report_stats(
+        options,
+        &[
+            ("scene", scene_time),
+            ("render", render_time),
+            ("output", output_time),
+        ],
+    )
+}
+
+/// Prints the ray counts and the time spent in each phase of the render,
+/// and writes them as JSON if asked to.
+fn report_stats(options: &Options, phases: &[(&str, Duration)]) -> std::io::Result<()> {
+    let stats = RenderStats::collect();
+    let render_time = phases
+        .iter()
+        .find(|(name, _)| *name == "render")
+        .map_or(Duration::from_secs(0), |(_, time)| *time);
+    println!(
+        "Traced {} camera rays, {} bounce rays and {} shadow rays, {:.0} rays per second",
+        stats.camera_rays,
+        stats.bounce_rays,
+        stats.shadow_rays,
+        stats.rays_per_second(render_time)
+    );
+    println!(
+        "Average path depth {:.2}, {:.1} intersection tests per ray",
+        stats.average_path_depth(),
+        stats.intersection_tests_per_ray()
+    );
+    for (name, time) in phases {
+        println!("{:>8}: {:.3}s", name, time.as_secs_f64());
+    }
+    if let Some(path) = &options.stats {
+        std::fs::write(path, stats.to_json(phases))?;
+    }
+    Ok(())
+}
 
-    let world = build_book_scene();
-    let rendered = trace_scene(&world, &camera, ny, nx, ns);
+/// Connects to a coordinator and renders the tiles it hands out, with one connection
+/// per thread sharing a single copy of the scene.
+fn run_worker(address: &str, threads: usize) -> std::io::Result<()> {
+    let mut connections = vec![WorkerConnection::connect(address)?];
+    let description = connections[0].scene().to_vec();
+    for _ in 1..threads {
+        connections.push(WorkerConnection::connect(address)?);
+    }
+    println!("Rendering for {} with {} threads", address, threads);
+
+    let scene = Options::parse(description).map_err(invalid_input)?;
+    let filter = scene.filter().map_err(invalid_input)?;
+    let animation = match &scene.animation {
+        Some(path) => Some(load_animation(path)?),
+        None => None,
+    };
+    let mut world = build_book_scene(scene.seed, animation.as_ref());
+    for light in scene.lights().map_err(invalid_input)? {
+        world.add_light(light);
+    }
+    let camera = build_camera(&scene, &world, animation.as_ref(), 0.0)?;
+    let mut renderer =
+        ProgressiveRenderer::new(&world, &*camera, scene.height, scene.width, &*filter);
+    renderer.set_seed(scene.seed);
+    if let Some(exposure) = &scene.exposure {
+        renderer.set_exposure(exposure.multiplier());
+    }
 
-    if let Some(image) = RgbImage::from_vec(
-        nx,
-        ny,
-        rendered
+    let renderer = &renderer;
+    let tiles: u32 = std::thread::scope(|scope| {
+        let handles: Vec<_> = connections
+            .iter_mut()
+            .map(|connection| scope.spawn(move || connection.serve(renderer)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| match handle.join().unwrap() {
+                Ok(tiles) => tiles,
+                Err(err) => {
+                    eprintln!("Lost connection to coordinator: {}", err);
+                    0
+                }
+            })
+            .sum()
+    });
+    println!("Rendered {} tiles", tiles);
+    Ok(())
+}
+
+/// Applies the shift, autofocus and tilt options to a perspective camera.
+/// The image is shifted first, so autofocus looks through the pixel as it will be rendered,
+/// and a tilted plane of focus pivots about the point autofocus found.
+fn adjust_lens(
+    options: &Options,
+    world: &World,
+    mut camera: PerspectiveCamera,
+) -> PerspectiveCamera {
+    if options.shift != (0.0, 0.0) {
+        camera = camera.with_shift(options.shift.0, options.shift.1);
+    }
+    let mut focus_point = None;
+    if let Some((x, y)) = options.focus_pixel {
+        // the pixel is in the whole image, which for stereo holds both eyes
+        let (mut u, mut v) = (
+            (x as f64 + 0.5) / options.width as f64,
+            1.0 - (y as f64 + 0.5) / options.height as f64,
+        );
+        match options.stereo {
+            Some(StereoLayout::SideBySide) => u = (u * 2.0).fract(),
+            Some(StereoLayout::TopBottom) => v = (v * 2.0).fract(),
+            None => {}
+        }
+        match camera.autofocus_point(world, u, v) {
+            Some(point) => {
+                let distance = camera.depth(point);
+                println!("Focusing {:.3} away", distance);
+                camera = camera.with_focus_distance(distance);
+                focus_point = Some(point);
+            }
+            None => eprintln!(
+                "Nothing to focus on at pixel {},{}, keeping the focus at {}",
+                x,
+                y,
+                camera.focus_distance()
+            ),
+        }
+    }
+    if options.tilt != 0.0 || options.swing != 0.0 {
+        camera = match focus_point {
+            Some(point) => camera.with_tilt_through(options.tilt, options.swing, point),
+            None => camera.with_tilt(options.tilt, options.swing),
+        };
+    }
+    camera
+}
+
+/// Builds the camera for the given time, following the animation's camera keys if there are any.
+fn build_camera(
+    options: &Options,
+    world: &World,
+    animation: Option<&Animation>,
+    time: f64,
+) -> std::io::Result<Box<dyn Camera>> {
+    let keys = animation.map(|animation| &animation.camera);
+    let look_from = keys
+        .and_then(|keys| keys.origin.at(time))
+        .map_or(Point3::new(13.0, 2.0, 3.0), Point3);
+    let look_at = keys
+        .and_then(|keys| keys.target.at(time))
+        .map_or(Point3::origin(), Point3);
+    let fov = keys.and_then(|keys| keys.fov.at(time)).or(options.fov);
+    let up = Vec3::new(0.0, 1.0, 0.0);
+    // each eye of a stereo pair gets half the image
+    let aspect = match options.stereo {
+        None => options.width as f64 / options.height as f64,
+        Some(StereoLayout::SideBySide) => options.width as f64 / 2.0 / options.height as f64,
+        Some(StereoLayout::TopBottom) => options.width as f64 * 2.0 / options.height as f64,
+    };
+    let dist_to_focus = keys
+        .and_then(|keys| keys.focus_distance.at(time))
+        .unwrap_or(10.0);
+    let aperture = 0.1;
+
+    let camera: Box<dyn Camera> = match options.projection {
+        Projection::Perspective => {
+            let mut camera = PerspectiveCamera::new(
+                look_from,
+                look_at,
+                up,
+                // curves can overshoot their keys, so keep to angles the camera can have
+                fov.map_or(20.0, |fov| fov.clamp(0.1, 179.9)),
+                aspect,
+                aperture,
+                dist_to_focus,
+            )
+            .with_shutter(time, time);
+            if let Some(exposure) = &options.exposure {
+                camera = camera.with_exposure(exposure, FULL_FRAME_SENSOR_HEIGHT);
+            }
+            camera = adjust_lens(options, world, camera);
+            if let Some(blades) = options.aperture_blades {
+                camera = camera.with_aperture(Aperture::Polygon {
+                    blades,
+                    rotation: options.aperture_rotation,
+                });
+            }
+            if let Some(path) = &options.aperture_mask {
+                camera = camera.with_aperture(Aperture::Mask(Arc::new(load_aperture_mask(path)?)));
+            }
+            match options.stereo {
+                Some(layout) => Box::new(StereoCamera::perspective(
+                    &camera,
+                    options.interocular,
+                    // converge where the camera ended up focused, e.g. by --focus-pixel
+                    options
+                        .convergence
+                        .unwrap_or_else(|| camera.focus_distance()),
+                    layout,
+                )),
+                None => Box::new(camera),
+            }
+        }
+        Projection::Orthographic => Box::new(
+            OrthographicCamera::new(
+                look_from,
+                look_at,
+                up,
+                options.view_width,
+                options.view_width / aspect,
+            )
+            .with_shutter(time, time),
+        ),
+        Projection::Equirectangular => {
+            let camera =
+                EquirectangularCamera::new(look_from, look_at, up).with_shutter(time, time);
+            match options.stereo {
+                Some(layout) => Box::new(StereoCamera::omnidirectional(
+                    &camera,
+                    options.interocular,
+                    layout,
+                )),
+                None => Box::new(camera),
+            }
+        }
+        Projection::CubeMap => {
+            Box::new(CubeMapCamera::new(look_from, look_at, up).with_shutter(time, time))
+        }
+        Projection::Realistic => {
+            let lens = match &options.lens {
+                Some(path) => Lens::parse(&std::fs::read_to_string(path)?)
+                    .map_err(|err| invalid_input(format!("{}: {}", path, err)))?,
+                None => Lens::double_gauss(),
+            };
+            // a 35mm film frame, cropped to the image's shape
+            let film = (f64::min(36.0, 24.0 * aspect), f64::min(24.0, 36.0 / aspect));
+            let camera = RealisticCamera::new(look_from, look_at, up, lens, film, dist_to_focus)
+                .ok_or_else(|| {
+                    invalid_input(format!("The lens can't focus at {}", dist_to_focus))
+                })?;
+            Box::new(camera.with_shutter(time, time))
+        }
+        Projection::Fisheye(mapping) => Box::new(
+            FisheyeCamera::new(
+                look_from,
+                look_at,
+                up,
+                fov.unwrap_or(180.0),
+                aspect,
+                mapping,
+            )
+            .with_shutter(time, time),
+        ),
+    };
+    Ok(camera)
+}
+
+/// Reads the keyframes for an animation, checking that the objects it moves are in the scene.
+fn load_animation(path: &str) -> std::io::Result<Animation> {
+    let animation = Animation::parse(&std::fs::read_to_string(path)?)
+        .map_err(|err| invalid_input(format!("{}: {}", path, err)))?;
+    for (name, _) in &animation.objects {
+        if !ANIMATED_SPHERES.contains(&name.as_str()) {
+            return Err(invalid_input(format!(
+                "{}: there is no {} to animate, only {}",
+                path,
+                name,
+                ANIMATED_SPHERES.join(", ")
+            )));
+        }
+    }
+    Ok(animation)
+}
+
+/// Reads a grayscale image to shape the lens opening.
+fn load_aperture_mask(path: &str) -> std::io::Result<ApertureMask> {
+    let image = image::open(path)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?
+        .to_luma();
+    let values: Vec<f64> = image.pixels().map(|p| p.data[0] as f64 / 255.0).collect();
+    ApertureMask::new(image.width(), image.height(), &values)
+        .ok_or_else(|| invalid_input(format!("The aperture mask {} lets no light through", path)))
+}
+
+fn invalid_input(message: String) -> Error {
+    Error::new(ErrorKind::InvalidInput, message)
+}
+
+/// Periodically saves checkpoints of a render so it can be resumed if the process dies.
+struct Checkpoints {
+    path: Option<String>,
+    interval: Duration,
+    last_saved: Instant,
+}
+
+impl Checkpoints {
+    fn after_pass(&mut self, renderer: &ProgressiveRenderer) -> std::io::Result<()> {
+        if self.last_saved.elapsed() >= self.interval {
+            self.save(renderer)?;
+        }
+        Ok(())
+    }
+
+    fn save(&mut self, renderer: &ProgressiveRenderer) -> std::io::Result<()> {
+        if let Some(path) = &self.path {
+            renderer.checkpoint()?.save(path)?;
+            self.last_saved = Instant::now();
+        }
+        Ok(())
+    }
+}
+
+/// Writes the render to the output, as linear colors for .exr files or as an 8-bit image otherwise.
+fn save_render(
+    options: &Options,
+    output: &str,
+    renderer: &ProgressiveRenderer,
+) -> std::io::Result<()> {
+    if options.writes_exr() {
+        exr::save_exr(
+            output,
+            options.width,
+            options.height,
+            &renderer.linear_image(),
+        )
+    } else {
+        save_image(options, output, &renderer.region(), &renderer.image())
+    }
+}
+
+/// Writes the rendered colors to the output image.
+/// When merging into an existing image, only the rendered region replaces its pixels.
+fn save_image(
+    options: &Options,
+    output: &str,
+    region: &Tile,
+    colors: &[Color],
+) -> std::io::Result<()> {
+    let (columns, rows) = (options.width, options.height);
+    if let Some(mut image) = RgbImage::from_vec(
+        columns,
+        rows,
+        colors
             .iter()
-            .flat_map(|v| v.0.iter())
+            .flat_map(|color| color.channels())
             .map(|c| (c * 255.99) as u8)
             .collect(),
     ) {
-        image.save("mapped-image.png")?;
+        if let Some(base) = &options.merge_into {
+            let mut merged = image::open(base)
+                .map_err(|err| Error::new(ErrorKind::InvalidData, err.to_string()))?
+                .to_rgb();
+            if merged.dimensions() != (columns, rows) {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "{} is {}x{} but the render is {}x{}",
+                        base,
+                        merged.width(),
+                        merged.height(),
+                        columns,
+                        rows
+                    ),
+                ));
+            }
+            for y in region.y..(region.y + region.height) {
+                for x in region.x..(region.x + region.width) {
+                    merged.put_pixel(x, y, *image.get_pixel(x, y));
+                }
+            }
+            image = merged;
+        }
+        image.save(output)?;
+        if options.projection == Projection::CubeMap {
+            save_cube_faces(output, &mut image)?;
+        }
     }
+    Ok(())
+}
 
+/// Writes each face of a cube map strip to its own file, named after the output
+/// with the face added, e.g. image_px.png.
+fn save_cube_faces(output: &str, image: &mut RgbImage) -> std::io::Result<()> {
+    let size = image.height();
+    let path = Path::new(output);
+    let stem = path
+        .file_stem()
+        .map_or("image".into(), |stem| stem.to_string_lossy());
+    let extension = path
+        .extension()
+        .map_or("png".into(), |ext| ext.to_string_lossy());
+    for (i, name) in CubeMapCamera::FACES.iter().enumerate() {
+        let face = image::imageops::crop(image, i as u32 * size, 0, size, size).to_image();
+        face.save(path.with_file_name(format!("{}_{}.{}", stem, name, extension)))?;
+    }
     Ok(())
 }
 
-fn build_book_scene() -> World {
+/// The big spheres in the book scene that an animation can move.
+const ANIMATED_SPHERES: [&str; 3] = ["glass", "diffuse", "metal"];
+
+fn build_book_scene(seed: u64, animation: Option<&Animation>) -> World {
     let mut world = World::new();
-    let mut rng = rand::thread_rng();
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut rand = || rng.gen::<f64>();
 
     world.push(Box::new(Sphere {
-        center: Vec3::new(0.0, -1000.0, 0.0),
+        center: Point3::new(0.0, -1000.0, 0.0),
         radius: 1000.0,
         material: Box::new(Lambertian {
-            albedo: Vec3::fill(0.5),
+            albedo: Color::fill(0.5),
         }),
     }));
 
@@ -62,15 +679,15 @@ fn build_book_scene() -> World {
             let a = a as f64;
             let b = b as f64;
             let material_choice: f64 = rand();
-            let center = Vec3::new(a + 0.9 * rand(), 0.2, b + 0.9 * rand());
+            let center = Point3::new(a + 0.9 * rand(), 0.2, b + 0.9 * rand());
 
-            if (center - Vec3::new(4.0, 0.2, 0.0)).length() > 0.9 {
+            if center.distance(Point3::new(4.0, 0.2, 0.0)) > 0.9 {
                 if material_choice < 0.8 {
                     world.push(Box::new(Sphere {
                         center,
                         radius: 0.2,
                         material: Box::new(Lambertian {
-                            albedo: Vec3::new(rand() * rand(), rand() * rand(), rand() * rand()),
+                            albedo: Color::new(rand() * rand(), rand() * rand(), rand() * rand()),
                         }),
                     }))
                 } else if material_choice < 0.95 {
@@ -78,7 +695,7 @@ fn build_book_scene() -> World {
                         center,
                         radius: 0.2,
                         material: Box::new(Metallic {
-                            albedo: Vec3::new(
+                            albedo: Color::new(
                                 0.5 * rand() * rand(),
                                 0.5 * rand() * rand(),
                                 0.5 * rand() * rand(),
@@ -99,30 +716,112 @@ fn build_book_scene() -> World {
         }
     }
 
-    world.push(Box::new(Sphere {
-        center: Vec3::new(0.0, 1.0, 0.0),
-        radius: 1.0,
-        material: Box::new(Dielectric {
-            refractive_index: 1.5,
-        }),
-    }));
+    let big_spheres = [
+        (
+            Point3::new(0.0, 1.0, 0.0),
+            Box::new(Dielectric {
+                refractive_index: 1.5,
+            }) as Box<dyn Scattering + Sync>,
+        ),
+        (
+            Point3::new(-4.0, 1.0, 0.0),
+            Box::new(Lambertian {
+                albedo: Color::new(0.4, 0.2, 0.1),
+            }),
+        ),
+        (
+            Point3::new(4.0, 1.0, 0.0),
+            Box::new(Metallic {
+                albedo: Color::new(0.7, 0.6, 0.5),
+                roughness: 0.0,
+            }),
+        ),
+    ];
+    for (name, (center, material)) in ANIMATED_SPHERES.iter().zip(big_spheres) {
+        let keys = animation.and_then(|animation| animation.object(name));
+        world.push(match keys {
+            None => Box::new(Sphere {
+                center,
+                radius: 1.0,
+                material,
+            }),
+            // keyframed at the origin, so it turns and scales about its own center
+            Some(keys) => {
+                let sphere = Sphere {
+                    center: Point3::origin(),
+                    radius: 1.0,
+                    material,
+                };
+                let moving = Keyframed::new(Arc::new(sphere), keys.clone());
+                Box::new(Transformed::new(
+                    Arc::new(moving),
+                    Transform::translation(center.0),
+                )) as Box<dyn Hitable + Sync>
+            }
+        });
+    }
 
-    world.push(Box::new(Sphere {
-        center: Vec3::new(-4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: Box::new(Lambertian {
-            albedo: Vec3::new(0.4, 0.2, 0.1),
-        }),
-    }));
+    world
+}
 
-    world.push(Box::new(Sphere {
-        center: Vec3::new(4.0, 1.0, 0.0),
-        radius: 1.0,
-        material: Box::new(Metallic {
-            albedo: Vec3::new(0.7, 0.6, 0.5),
-            roughness: 0.0,
-        }),
-    }));
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use path_tracing::dot;
 
-    world
+    fn options(args: &[&str]) -> Options {
+        Options::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn autofocus_looks_through_the_shifted_image() {
+        let mut world = World::new();
+        for (center, radius) in [
+            (Point3::new(0.0, 0.0, -4.0), 0.5),
+            (Point3::new(0.0, 6.0, -6.0), 1.0),
+        ] {
+            world.push(Box::new(Sphere {
+                center,
+                radius,
+                material: Box::new(Lambertian {
+                    albedo: Color::fill(0.5),
+                }),
+            }));
+        }
+        let camera = PerspectiveCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.1,
+            10.0,
+        );
+        // an odd size puts the middle of the focus pixel at the center of the image
+        let args = [
+            "--width",
+            "101",
+            "--height",
+            "101",
+            "--focus-pixel",
+            "50,50",
+        ];
+
+        // unshifted, the middle of the image sees the near sphere
+        let centered = adjust_lens(&options(&args), &world, camera.clone());
+        assert!((centered.focus_distance() - 3.5).abs() < 1e-9);
+
+        // shifted up half the image, it sees the high sphere 45 degrees up
+        let shifted_args = [&args[..], &["--shift", "0,0.5"]].concat();
+        let shifted = adjust_lens(&options(&shifted_args), &world, camera.clone());
+        let front = 6.0 - f64::sqrt(0.5);
+        assert!((shifted.focus_distance() - front).abs() < 1e-9);
+
+        // and a tilted plane of focus still passes through the point focused on
+        let tilted_args = [&shifted_args[..], &["--tilt", "20"]].concat();
+        let tilted = adjust_lens(&options(&tilted_args), &world, camera);
+        let (point, normal) = tilted.focal_plane.unwrap();
+        let focused = Point3::new(0.0, front, -front);
+        assert!(dot(focused - point, normal).abs() < 1e-9);
+    }
 }
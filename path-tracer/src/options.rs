@@ -0,0 +1,880 @@
+use path_tracing::{
+    BoxFilter, Color, DirectionalLight, Exposure, Filter, FisheyeMapping, GaussianFilter,
+    LanczosFilter, Light, MitchellFilter, Point3, PointLight, SpotLight, StereoLayout, TentFilter,
+    Tile, TileOrder, Vec3,
+};
+use std::time::Duration;
+
+pub const USAGE: &str = "Usage: path-tracer [options]
+
+Options:
+  --width <px>             image width (default 900)
+  --height <px>            image height (default 600)
+  --samples <n>            samples per pixel (default 100)
+  --pass-samples <n>       samples per pixel in each progressive pass; the image is
+                           written after every pass (default: all samples in one pass)
+  --time <duration>        render until the time budget runs out instead of a fixed
+                           sample count, e.g. 90s, 5m or 1.5h
+  --tile-size <px>         width and height of render tiles (default 32)
+  --tile-order <order>     order tiles are rendered in: scanline, spiral or hilbert
+                           (default spiral)
+  --region <x,y,w,h>       only render this rectangle of pixels
+  --merge-into <path>      write the rendered region over a copy of an existing image
+  --seed <n>               seed for the scene and sampling (default 0)
+  --checkpoint <path>      periodically save the render progress to this file
+  --checkpoint-interval <duration>
+                           time between checkpoints (default 5m)
+  --resume <path>          continue the render saved in a checkpoint file; new
+                           checkpoints go to the same file unless --checkpoint is given
+  --coordinator <address>  listen on address (e.g. 0.0.0.0:7878) and render with the
+                           workers that connect instead of locally
+  --worker <address>       render tiles for the coordinator at address; the scene and
+                           image settings come from the coordinator
+  --threads <n>            connections a worker opens, each rendering one tile at a time
+                           (default: the number of CPUs)
+  --output <path>          image file to write (default mapped-image.png); .exr files
+                           hold the linear colors as floats
+  --stats <path>           also write the render statistics to this file as JSON
+  --filter <name>          reconstruction filter: box, tent, gaussian, mitchell or lanczos (default box)
+  --filter-radius <px>     filter radius in pixels (defaults depend on the filter)
+  --projection <name>      camera projection: perspective, orthographic, equirectangular,
+                           cubemap, fisheye-equidistant, fisheye-equisolid,
+                           fisheye-stereographic or realistic (default perspective); a cube
+                           map is a strip of six faces, each also written to its own file,
+                           e.g. image_px.png
+  --lens <path>            lens prescription for the realistic projection, with a line per
+                           surface giving its radius, thickness, ior and aperture in mm
+                           (default: a 50mm double-Gauss lens)
+  --fov <degrees>          field of view of a perspective (default 20) or fisheye
+                           (default 180, at most 360) camera
+  --view-width <units>     width of the scene seen by an orthographic camera (default 8)
+  --animation <path>       keyframes for the camera and the glass, diffuse and metal
+                           spheres, which move relative to their place in the scene
+  --frames <first-last>    render these frames of the animation, each to the --output
+                           path with its #s replaced by the frame number, e.g.
+                           frame_####.exr
+  --fps <n>                frames per second of the animation (default 24)
+  --light <spec>           add a light, and repeat for more; one of
+                           point,x,y,z,intensity
+                           spot,x,y,z,target x,y,z,intensity,cone degrees,full-strength degrees
+                           sun,direction x,y,z,irradiance,angular radius degrees";
+
+/// How the camera projects the scene onto the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+    Equirectangular,
+    CubeMap,
+    Fisheye(FisheyeMapping),
+    Realistic,
+}
+
+impl Projection {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+            Projection::Equirectangular => "equirectangular",
+            Projection::CubeMap => "cubemap",
+            Projection::Fisheye(FisheyeMapping::Equidistant) => "fisheye-equidistant",
+            Projection::Fisheye(FisheyeMapping::Equisolid) => "fisheye-equisolid",
+            Projection::Fisheye(FisheyeMapping::Stereographic) => "fisheye-stereographic",
+            Projection::Realistic => "realistic",
+        }
+    }
+}
+
+/// Settings for a render, gathered from the command line.
+pub struct Options {
+    pub width: u32,
+    pub height: u32,
+    pub samples: u32,
+    pub pass_samples: Option<u32>,
+    pub time: Option<Duration>,
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    pub region: Option<Tile>,
+    pub merge_into: Option<String>,
+    pub seed: u64,
+    pub checkpoint: Option<String>,
+    pub checkpoint_interval: Duration,
+    pub resume: Option<String>,
+    pub coordinator: Option<String>,
+    pub worker: Option<String>,
+    pub threads: usize,
+    pub output: String,
+    pub stats: Option<String>,
+    pub filter: String,
+    pub filter_radius: Option<f64>,
+    pub projection: Projection,
+    pub view_width: f64,
+    pub fov: Option<f64>,
+    pub stereo: Option<StereoLayout>,
+    pub interocular: f64,
+    pub convergence: Option<f64>,
+    pub exposure: Option<Exposure>,
+    pub aperture_blades: Option<u32>,
+    pub aperture_rotation: f64,
+    pub aperture_mask: Option<String>,
+    pub lens: Option<String>,
+    pub focus_pixel: Option<(u32, u32)>,
+    pub shift: (f64, f64),
+    pub tilt: f64,
+    pub swing: f64,
+    pub animation: Option<String>,
+    pub frames: Option<(u32, u32)>,
+    pub fps: f64,
+    pub lights: Vec<String>,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            width: 900,
+            height: 600,
+            samples: 100,
+            pass_samples: None,
+            time: None,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            region: None,
+            merge_into: None,
+            seed: 0,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(300),
+            resume: None,
+            coordinator: None,
+            worker: None,
+            threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            output: String::from("mapped-image.png"),
+            stats: None,
+            filter: String::from("box"),
+            filter_radius: None,
+            projection: Projection::Perspective,
+            view_width: 8.0,
+            fov: None,
+            stereo: None,
+            interocular: 0.065,
+            convergence: None,
+            exposure: None,
+            aperture_blades: None,
+            aperture_rotation: 0.0,
+            aperture_mask: None,
+            lens: None,
+            focus_pixel: None,
+            shift: (0.0, 0.0),
+            tilt: 0.0,
+            swing: 0.0,
+            animation: None,
+            frames: None,
+            fps: 24.0,
+            lights: vec![],
+        }
+    }
+}
+
+impl Options {
+    pub fn parse<I>(args: I) -> Result<Options, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+            match arg.as_str() {
+                "--width" => options.width = parse_count(&value()?)?,
+                "--height" => options.height = parse_count(&value()?)?,
+                "--samples" => options.samples = parse_count(&value()?)?,
+                "--pass-samples" => options.pass_samples = Some(parse_count(&value()?)?),
+                "--time" => options.time = Some(parse_duration(&value()?)?),
+                "--tile-size" => options.tile_size = parse_count(&value()?)?,
+                "--tile-order" => {
+                    options.tile_order = match value()?.as_str() {
+                        "scanline" => TileOrder::Scanline,
+                        "spiral" => TileOrder::Spiral,
+                        "hilbert" => TileOrder::Hilbert,
+                        order => return Err(format!("Unknown tile order {}", order)),
+                    }
+                }
+                "--region" => options.region = Some(parse_region(&value()?)?),
+                "--merge-into" => options.merge_into = Some(value()?),
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = seed
+                        .parse()
+                        .map_err(|_| format!("Expected an integer seed, got {}", seed))?
+                }
+                "--checkpoint" => options.checkpoint = Some(value()?),
+                "--checkpoint-interval" => options.checkpoint_interval = parse_duration(&value()?)?,
+                "--resume" => options.resume = Some(value()?),
+                "--coordinator" => options.coordinator = Some(value()?),
+                "--worker" => options.worker = Some(value()?),
+                "--threads" => options.threads = parse_count(&value()?)? as usize,
+                "--output" => options.output = value()?,
+                "--stats" => options.stats = Some(value()?),
+                "--filter" => options.filter = value()?,
+                "--filter-radius" => options.filter_radius = Some(parse_number(&value()?)?),
+                "--projection" => {
+                    options.projection = match value()?.as_str() {
+                        "perspective" => Projection::Perspective,
+                        "orthographic" => Projection::Orthographic,
+                        "equirectangular" => Projection::Equirectangular,
+                        "cubemap" => Projection::CubeMap,
+                        "fisheye-equidistant" => Projection::Fisheye(FisheyeMapping::Equidistant),
+                        "fisheye-equisolid" => Projection::Fisheye(FisheyeMapping::Equisolid),
+                        "fisheye-stereographic" => {
+                            Projection::Fisheye(FisheyeMapping::Stereographic)
+                        }
+                        "realistic" => Projection::Realistic,
+                        name => return Err(format!("Unknown projection {}", name)),
+                    }
+                }
+                "--view-width" => options.view_width = parse_number(&value()?)?,
+                "--fov" => options.fov = Some(parse_number(&value()?)?),
+                "--stereo" => {
+                    options.stereo = Some(match value()?.as_str() {
+                        "top-bottom" => StereoLayout::TopBottom,
+                        "side-by-side" => StereoLayout::SideBySide,
+                        layout => return Err(format!("Unknown stereo layout {}", layout)),
+                    })
+                }
+                "--interocular" => options.interocular = parse_number(&value()?)?,
+                "--convergence" => options.convergence = Some(parse_number(&value()?)?),
+                "--f-stop" => options.exposure_mut().f_stop = parse_number(&value()?)?,
+                "--shutter-speed" => {
+                    options.exposure_mut().shutter_speed = parse_shutter_speed(&value()?)?
+                }
+                "--iso" => options.exposure_mut().iso = parse_number(&value()?)?,
+                "--aperture-blades" => match parse_count(&value()?)? {
+                    blades if blades >= 3 => options.aperture_blades = Some(blades),
+                    _ => return Err(String::from("An aperture needs at least 3 blades")),
+                },
+                "--aperture-rotation" => options.aperture_rotation = parse_angle(&value()?)?,
+                "--aperture-mask" => options.aperture_mask = Some(value()?),
+                "--lens" => options.lens = Some(value()?),
+                "--focus-pixel" => options.focus_pixel = Some(parse_pixel(&value()?)?),
+                "--shift" => options.shift = parse_shift(&value()?)?,
+                "--tilt" => options.tilt = parse_angle(&value()?)?,
+                "--swing" => options.swing = parse_angle(&value()?)?,
+                "--animation" => options.animation = Some(value()?),
+                "--frames" => options.frames = Some(parse_frames(&value()?)?),
+                "--fps" => options.fps = parse_number(&value()?)?,
+                "--light" => options.lights.push(value()?),
+                _ => return Err(format!("Unknown option {}", arg)),
+            }
+        }
+        if options.coordinator.is_some() && options.time.is_some() {
+            return Err(String::from("--time can't be used with --coordinator"));
+        }
+        if options.merge_into.is_some() && options.region.is_none() {
+            return Err(String::from("--merge-into needs a --region to merge"));
+        }
+        if options.projection == Projection::CubeMap && options.width != 6 * options.height {
+            return Err(String::from(
+                "--projection cubemap needs --width to be six times --height",
+            ));
+        }
+        match (options.projection, options.fov) {
+            (Projection::Fisheye(FisheyeMapping::Stereographic), Some(fov)) if fov >= 360.0 => {
+                return Err(String::from(
+                    "A stereographic fisheye needs a --fov under 360 degrees",
+                ))
+            }
+            (Projection::Fisheye(_), Some(fov)) if fov > 360.0 => {
+                return Err(String::from("A fisheye --fov can be at most 360 degrees"))
+            }
+            (Projection::Perspective, Some(fov)) if fov >= 180.0 => {
+                return Err(String::from(
+                    "A perspective --fov must be under 180 degrees",
+                ))
+            }
+            _ => {}
+        }
+        if options.stereo.is_some()
+            && !matches!(
+                options.projection,
+                Projection::Perspective | Projection::Equirectangular
+            )
+        {
+            return Err(format!(
+                "--stereo can't be used with the {} projection",
+                options.projection.name()
+            ));
+        }
+        if let Some((x, y)) = options.focus_pixel {
+            if options.projection != Projection::Perspective {
+                return Err(String::from(
+                    "--focus-pixel needs the perspective projection",
+                ));
+            }
+            if x >= options.width || y >= options.height {
+                return Err(format!(
+                    "--focus-pixel {},{} is outside the {}x{} image",
+                    x, y, options.width, options.height
+                ));
+            }
+        }
+        let tilt_shift = options.shift != (0.0, 0.0) || options.tilt != 0.0 || options.swing != 0.0;
+        if tilt_shift && options.projection != Projection::Perspective {
+            return Err(String::from(
+                "--shift, --tilt and --swing need the perspective projection",
+            ));
+        }
+        let lens_settings = [
+            (
+                "--f-stop, --shutter-speed and --iso need",
+                options.exposure.is_some(),
+            ),
+            ("--aperture-blades needs", options.aperture_blades.is_some()),
+            ("--aperture-mask needs", options.aperture_mask.is_some()),
+        ];
+        if options.projection != Projection::Perspective {
+            if let Some((names, _)) = lens_settings.iter().find(|(_, given)| *given) {
+                return Err(format!("{} the perspective projection", names));
+            }
+        }
+        if options.aperture_blades.is_some() && options.aperture_mask.is_some() {
+            return Err(String::from(
+                "--aperture-blades and --aperture-mask can't be used together",
+            ));
+        }
+        if options.frames.is_some() {
+            if !options.output.contains('#') {
+                return Err(String::from(
+                    "--frames needs an --output with #s for the frame number, e.g. frame_####.exr",
+                ));
+            }
+            let still_only = [
+                ("--coordinator", options.coordinator.is_some()),
+                ("--time", options.time.is_some()),
+                ("--checkpoint", options.checkpoint.is_some()),
+                ("--resume", options.resume.is_some()),
+                ("--merge-into", options.merge_into.is_some()),
+            ];
+            if let Some((name, _)) = still_only.iter().find(|(_, given)| *given) {
+                return Err(format!("{} can't be used with --frames", name));
+            }
+        }
+        if options.writes_exr() && options.merge_into.is_some() {
+            return Err(String::from("--merge-into can't write an .exr --output"));
+        }
+        if options.writes_exr() && options.projection == Projection::CubeMap {
+            return Err(String::from(
+                "--projection cubemap can't write an .exr --output",
+            ));
+        }
+        // build the filter and lights once up front so mistakes are reported before rendering
+        options.filter()?;
+        options.lights()?;
+        Ok(options)
+    }
+
+    /// The options a worker needs to build the same scene, camera and filter,
+    /// as command line arguments.
+    pub fn scene_description(&self, seed: u64) -> Vec<String> {
+        let mut description = vec![];
+        let mut push = |option: &str, value: String| {
+            description.push(option.to_string());
+            description.push(value);
+        };
+        push("--width", self.width.to_string());
+        push("--height", self.height.to_string());
+        push("--seed", seed.to_string());
+        push("--filter", self.filter.clone());
+        if let Some(radius) = self.filter_radius {
+            push("--filter-radius", radius.to_string());
+        }
+        if let Some(fov) = self.fov {
+            push("--fov", fov.to_string());
+        }
+        if let Some(layout) = self.stereo {
+            let layout = match layout {
+                StereoLayout::TopBottom => "top-bottom",
+                StereoLayout::SideBySide => "side-by-side",
+            };
+            push("--stereo", layout.to_string());
+            push("--interocular", self.interocular.to_string());
+        }
+        if let Some(convergence) = self.convergence {
+            push("--convergence", convergence.to_string());
+        }
+        if let Some(exposure) = self.exposure {
+            push("--f-stop", exposure.f_stop.to_string());
+            push("--shutter-speed", exposure.shutter_speed.to_string());
+            push("--iso", exposure.iso.to_string());
+        }
+        if let Some(blades) = self.aperture_blades {
+            push("--aperture-blades", blades.to_string());
+            push("--aperture-rotation", self.aperture_rotation.to_string());
+        }
+        // workers need their own copy of the mask at the same path
+        if let Some(mask) = &self.aperture_mask {
+            push("--aperture-mask", mask.clone());
+        }
+        if let Some((x, y)) = self.focus_pixel {
+            push("--focus-pixel", format!("{},{}", x, y));
+        }
+        if self.shift != (0.0, 0.0) {
+            push("--shift", format!("{},{}", self.shift.0, self.shift.1));
+        }
+        if self.tilt != 0.0 || self.swing != 0.0 {
+            push("--tilt", self.tilt.to_string());
+            push("--swing", self.swing.to_string());
+        }
+        if let Some(lens) = &self.lens {
+            push("--lens", lens.clone());
+        }
+        for light in &self.lights {
+            push("--light", light.clone());
+        }
+        if let Some(animation) = &self.animation {
+            push("--animation", animation.clone());
+        }
+        if self.projection != Projection::Perspective {
+            push("--projection", self.projection.name().to_string());
+            push("--view-width", self.view_width.to_string());
+        }
+        description
+    }
+
+    /// Whether the output is an OpenEXR image of linear colors rather than an 8-bit image.
+    pub fn writes_exr(&self) -> bool {
+        self.output.to_lowercase().ends_with(".exr")
+    }
+
+    /// The output path for a frame of an animation, with the run of #s replaced by
+    /// the frame number padded to the same width.
+    pub fn frame_output(&self, frame: u32) -> String {
+        let start = self.output.find('#').unwrap_or(self.output.len());
+        let digits = self.output[start..]
+            .chars()
+            .take_while(|&c| c == '#')
+            .count();
+        format!(
+            "{}{:0width$}{}",
+            &self.output[..start],
+            frame,
+            &self.output[start + digits..],
+            width = digits
+        )
+    }
+
+    /// The exposure settings, starting from f/16, 1/100s and ISO 100 if none were given yet.
+    fn exposure_mut(&mut self) -> &mut Exposure {
+        self.exposure.get_or_insert(Exposure {
+            f_stop: 16.0,
+            shutter_speed: 1.0 / 100.0,
+            iso: 100.0,
+        })
+    }
+
+    /// Returns the reconstruction filter named on the command line.
+    pub fn filter(&self) -> Result<Box<dyn Filter + Sync>, String> {
+        let radius = self.filter_radius;
+        let filter: Box<dyn Filter + Sync> = match self.filter.as_str() {
+            "box" => Box::new(BoxFilter {
+                radius: radius.unwrap_or(0.5),
+            }),
+            "tent" => Box::new(TentFilter {
+                radius: radius.unwrap_or(1.0),
+            }),
+            "gaussian" => Box::new(GaussianFilter {
+                radius: radius.unwrap_or(1.5),
+                alpha: 2.0,
+            }),
+            "mitchell" => Box::new(MitchellFilter {
+                radius: radius.unwrap_or(2.0),
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            "lanczos" => Box::new(LanczosFilter {
+                radius: radius.unwrap_or(3.0),
+                tau: 3.0,
+            }),
+            name => return Err(format!("Unknown filter {}", name)),
+        };
+        Ok(filter)
+    }
+
+    /// Returns the lights given on the command line.
+    pub fn lights(&self) -> Result<Vec<Box<dyn Light>>, String> {
+        self.lights.iter().map(|spec| parse_light(spec)).collect()
+    }
+}
+
+/// Parses a light given as its kind followed by comma separated numbers.
+fn parse_light(spec: &str) -> Result<Box<dyn Light>, String> {
+    let error = || format!("Expected a light like point,0,4,0,20, got {}", spec);
+    let (kind, numbers) = spec.split_once(',').ok_or_else(error)?;
+    let numbers: Vec<f64> = numbers
+        .split(',')
+        .map(|n| n.trim().parse::<f64>().ok().filter(|n| n.is_finite()))
+        .collect::<Option<_>>()
+        .ok_or_else(error)?;
+    let no_direction = || format!("A light needs a direction, got {}", spec);
+    let light: Box<dyn Light> = match (kind, numbers.as_slice()) {
+        ("point", &[x, y, z, intensity]) => Box::new(PointLight {
+            position: Point3::new(x, y, z),
+            intensity: Color::fill(intensity),
+        }),
+        ("spot", &[x, y, z, tx, ty, tz, intensity, cone, full]) => {
+            if !(0.0 < cone && cone <= 180.0 && 0.0 <= full && full <= cone) {
+                return Err(format!(
+                    "A spot light needs a cone between 0 and 180 degrees, wider than its \
+                     full-strength angle, got {}",
+                    spec
+                ));
+            }
+            let position = Point3::new(x, y, z);
+            let direction = Point3::new(tx, ty, tz) - position;
+            if direction.length() == 0.0 {
+                return Err(no_direction());
+            }
+            Box::new(SpotLight {
+                position,
+                direction,
+                intensity: Color::fill(intensity),
+                cone_angle: cone,
+                falloff_angle: full,
+            })
+        }
+        ("sun", &[x, y, z, irradiance, radius]) if (0.0..90.0).contains(&radius) => {
+            if Vec3::new(x, y, z).length() == 0.0 {
+                return Err(no_direction());
+            }
+            Box::new(DirectionalLight {
+                direction: Vec3::new(x, y, z),
+                irradiance: Color::fill(irradiance),
+                angular_radius: radius,
+            })
+        }
+        _ => return Err(error()),
+    };
+    Ok(light)
+}
+
+fn parse_number(text: &str) -> Result<f64, String> {
+    match text.parse::<f64>() {
+        Ok(n) if n > 0.0 => Ok(n),
+        _ => Err(format!("Expected a positive number, got {}", text)),
+    }
+}
+
+fn parse_count(text: &str) -> Result<u32, String> {
+    match text.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!("Expected a positive integer, got {}", text)),
+    }
+}
+
+/// Parses a shutter speed in seconds, either as a number or a fraction like 1/125.
+fn parse_shutter_speed(text: &str) -> Result<f64, String> {
+    let error = || format!("Expected a shutter speed like 1/125 or 0.5, got {}", text);
+    match text.split_once('/') {
+        Some((numerator, denominator)) => {
+            let numerator = parse_number(numerator).map_err(|_| error())?;
+            let denominator = parse_number(denominator).map_err(|_| error())?;
+            Ok(numerator / denominator)
+        }
+        None => parse_number(text).map_err(|_| error()),
+    }
+}
+
+fn parse_angle(text: &str) -> Result<f64, String> {
+    text.parse()
+        .map_err(|_| format!("Expected an angle in degrees, got {}", text))
+}
+
+/// Parses a pixel position given as x,y.
+fn parse_pixel(text: &str) -> Result<(u32, u32), String> {
+    let numbers: Vec<u32> = text
+        .split(',')
+        .map(|n| n.trim().parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Expected a pixel like 450,300, got {}", text))?;
+    match numbers.as_slice() {
+        &[x, y] => Ok((x, y)),
+        _ => Err(format!("Expected a pixel like 450,300, got {}", text)),
+    }
+}
+
+/// Parses a lens shift given as x,y fractions of the image size.
+fn parse_shift(text: &str) -> Result<(f64, f64), String> {
+    let numbers: Vec<f64> = text
+        .split(',')
+        .map(|n| n.trim().parse::<f64>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Expected a shift like 0,0.2, got {}", text))?;
+    match numbers.as_slice() {
+        &[x, y] => Ok((x, y)),
+        _ => Err(format!("Expected a shift like 0,0.2, got {}", text)),
+    }
+}
+
+/// Parses an inclusive range of frames given as first-last, or a single frame.
+fn parse_frames(text: &str) -> Result<(u32, u32), String> {
+    let error = || format!("Expected frames like 1-48, got {}", text);
+    let (first, last) = text.split_once('-').unwrap_or((text, text));
+    let first: u32 = first.trim().parse().map_err(|_| error())?;
+    let last: u32 = last.trim().parse().map_err(|_| error())?;
+    if last < first {
+        return Err(error());
+    }
+    Ok((first, last))
+}
+
+/// Parses a pixel rectangle given as x,y,width,height.
+fn parse_region(text: &str) -> Result<Tile, String> {
+    let numbers: Vec<u32> = text
+        .split(',')
+        .map(|n| n.trim().parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Expected a region like 10,20,64,48, got {}", text))?;
+    match numbers.as_slice() {
+        &[x, y, width, height]
+            if width > 0
+                && height > 0
+                && x.checked_add(width).is_some()
+                && y.checked_add(height).is_some() =>
+        {
+            Ok(Tile {
+                x,
+                y,
+                width,
+                height,
+            })
+        }
+        _ => Err(format!("Expected a region like 10,20,64,48, got {}", text)),
+    }
+}
+
+/// The longest duration accepted, so adding one to the current time can't overflow.
+const LONGEST_DURATION: Duration = Duration::from_secs(366 * 24 * 3600);
+
+/// Parses durations given in seconds, minutes or hours, e.g. 90s, 5m or 1.5h.
+/// Numbers without a unit are seconds. Durations over a year are rejected.
+fn parse_duration(text: &str) -> Result<Duration, String> {
+    let (number, scale) = match text.chars().last() {
+        Some('s') => (&text[..text.len() - 1], 1.0),
+        Some('m') => (&text[..text.len() - 1], 60.0),
+        Some('h') => (&text[..text.len() - 1], 3600.0),
+        _ => (text, 1.0),
+    };
+    match number.parse::<f64>() {
+        Ok(n) if n > 0.0 && n * scale <= LONGEST_DURATION.as_secs_f64() => {
+            Ok(Duration::from_millis((n * scale * 1000.0) as u64))
+        }
+        _ => Err(format!(
+            "Expected a duration like 90s, 5m or 1.5h, up to a year, got {}",
+            text
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("1.5h"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("20"), Ok(Duration::from_secs(20)));
+        assert!(parse_duration("5d").is_err());
+        assert!(parse_duration("-1m").is_err());
+        assert!(parse_duration("inf").is_err());
+        assert!(parse_duration("NaNs").is_err());
+        assert!(parse_duration("1e30h").is_err());
+        assert_eq!(parse_duration("8784h"), Ok(LONGEST_DURATION));
+    }
+
+    #[test]
+    fn scene_descriptions_keep_spaces_in_paths() {
+        let options = parse(&["--aperture-mask", "my masks/hex.pgm"]).unwrap();
+        let description = options.scene_description(0);
+        assert!(description.iter().any(|arg| arg == "my masks/hex.pgm"));
+        let scene = Options::parse(description).unwrap();
+        assert_eq!(scene.aperture_mask, Some(String::from("my masks/hex.pgm")));
+    }
+
+    #[test]
+    fn render_options() {
+        let options = parse(&["--time", "5m", "--filter", "gaussian"]).unwrap();
+        assert_eq!(options.time, Some(Duration::from_secs(300)));
+        assert_eq!(options.filter().unwrap().radius(), 1.5);
+
+        let options = parse(&["--region", "10,20,64,48", "--merge-into", "a.png"]).unwrap();
+        assert_eq!(
+            options.region,
+            Some(Tile {
+                x: 10,
+                y: 20,
+                width: 64,
+                height: 48
+            })
+        );
+        assert!(parse(&["--merge-into", "a.png"]).is_err());
+        assert!(parse(&["--region", "1,2,3"]).is_err());
+        assert!(parse(&["--region", "1,2,0,4"]).is_err());
+        assert!(parse(&["--region", "4294967295,2,3,4"]).is_err());
+
+        assert!(parse(&["--coordinator", "0.0.0.0:7878", "--time", "5m"]).is_err());
+        assert!(parse(&[
+            "--region",
+            "0,0,1,1",
+            "--merge-into",
+            "a.png",
+            "--output",
+            "b.exr"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn scene_descriptions_rebuild_the_scene() {
+        let options = parse(&[
+            "--width",
+            "64",
+            "--filter",
+            "tent",
+            "--filter-radius",
+            "1.5",
+        ])
+        .unwrap();
+        let scene = Options::parse(options.scene_description(7)).unwrap();
+        assert_eq!((scene.width, scene.height, scene.seed), (64, 600, 7));
+        assert_eq!(scene.filter().unwrap().radius(), 1.5);
+    }
+
+    #[test]
+    fn projection_options() {
+        let options = parse(&["--projection", "orthographic", "--view-width", "12"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(scene.projection, Projection::Orthographic);
+        assert_eq!(scene.view_width, 12.0);
+        assert!(parse(&["--projection", "fisheye"]).is_err());
+        let options = parse(&["--projection", "fisheye-equisolid", "--fov", "200"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(
+            scene.projection,
+            Projection::Fisheye(FisheyeMapping::Equisolid)
+        );
+        assert_eq!(scene.fov, Some(200.0));
+        assert!(parse(&["--projection", "fisheye-equidistant", "--fov", "400"]).is_err());
+        assert!(parse(&["--projection", "fisheye-stereographic", "--fov", "360"]).is_err());
+        assert!(parse(&["--fov", "180"]).is_err());
+
+        assert!(parse(&["--projection", "cubemap"]).is_err());
+        let options = parse(&["--projection", "cubemap", "--width", "60", "--height", "10"]);
+        assert_eq!(options.unwrap().projection, Projection::CubeMap);
+    }
+
+    #[test]
+    fn stereo_options() {
+        let options = parse(&["--stereo", "side-by-side", "--convergence", "4"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(scene.stereo, Some(StereoLayout::SideBySide));
+        assert_eq!((scene.interocular, scene.convergence), (0.065, Some(4.0)));
+        assert!(parse(&["--stereo", "top-bottom", "--projection", "orthographic"]).is_err());
+        assert!(parse(&["--stereo", "anaglyph"]).is_err());
+    }
+
+    #[test]
+    fn exposure_options() {
+        assert_eq!(parse(&[]).unwrap().exposure, None);
+        let options = parse(&["--shutter-speed", "1/125", "--iso", "400"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(
+            scene.exposure,
+            Some(Exposure {
+                f_stop: 16.0,
+                shutter_speed: 0.008,
+                iso: 400.0
+            })
+        );
+        assert_eq!(parse_shutter_speed("2"), Ok(2.0));
+        assert!(parse_shutter_speed("1/0").is_err());
+        assert!(parse(&["--f-stop", "0"]).is_err());
+        assert!(parse(&["--iso", "400", "--projection", "cubemap"]).is_err());
+    }
+
+    #[test]
+    fn lens_options() {
+        let options = parse(&["--aperture-blades", "6", "--aperture-rotation", "-15"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(
+            (scene.aperture_blades, scene.aperture_rotation),
+            (Some(6), -15.0)
+        );
+        assert!(parse(&["--aperture-blades", "2"]).is_err());
+
+        let options = parse(&["--shift", "0,0.2", "--tilt", "-5"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(
+            (scene.shift, scene.tilt, scene.swing),
+            ((0.0, 0.2), -5.0, 0.0)
+        );
+        assert!(parse(&["--shift", "0.2"]).is_err());
+
+        let options = parse(&["--focus-pixel", "450,300"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(scene.focus_pixel, Some((450, 300)));
+        assert!(parse(&["--focus-pixel", "900,0"]).is_err());
+        assert!(parse(&["--focus-pixel", "-1,0"]).is_err());
+        assert!(parse(&["--focus-pixel", "1,1", "--projection", "orthographic"]).is_err());
+        assert!(parse(&["--tilt", "5", "--projection", "equirectangular"]).is_err());
+        assert!(parse(&["--aperture-blades", "6", "--projection", "orthographic"]).is_err());
+        assert!(parse(&["--aperture-mask", "hex.pgm", "--projection", "realistic"]).is_err());
+        let options = parse(&["--projection", "realistic", "--lens", "wide.txt"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(scene.projection, Projection::Realistic);
+        assert_eq!(scene.lens, Some(String::from("wide.txt")));
+        assert!(parse(&["--aperture-blades", "5", "--aperture-mask", "heart.png"]).is_err());
+    }
+
+    #[test]
+    fn animation_options() {
+        let options = parse(&["--frames", "1-48", "--output", "frame_####.exr"]).unwrap();
+        assert_eq!(options.frames, Some((1, 48)));
+        assert!(options.writes_exr());
+        assert_eq!(options.frame_output(7), "frame_0007.exr");
+        assert_eq!(options.frame_output(12345), "frame_12345.exr");
+        assert_eq!(parse_frames("5"), Ok((5, 5)));
+        assert!(parse_frames("9-3").is_err());
+        assert!(parse(&["--frames", "1-48"]).is_err());
+        assert!(parse(&["--frames", "1-2", "--output", "f#.png", "--time", "5m"]).is_err());
+        let options = parse(&["--animation", "move.txt", "--fps", "30"]).unwrap();
+        assert_eq!(options.fps, 30.0);
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(scene.animation, Some(String::from("move.txt")));
+    }
+
+    #[test]
+    fn light_options() {
+        let options = parse(&["--light", "point,0,4,0,20", "--light", "sun,1,1,0,2,0.27"]).unwrap();
+        let scene = Options::parse(options.scene_description(0)).unwrap();
+        assert_eq!(scene.lights, options.lights);
+        assert_eq!(scene.lights().unwrap().len(), 2);
+        assert!(parse(&["--light", "spot,0,4,0,0,0,0,20,30,20"]).is_ok());
+        assert!(parse(&["--light", "spot,0,4,0,0,0,0,20,20,30"]).is_err());
+        assert!(parse(&["--light", "point,0,4,0"]).is_err());
+        assert!(parse(&["--light", "area,0,4,0,1"]).is_err());
+        assert!(parse(&["--light", "spot,0,4,0,0,4,0,20,30,20"]).is_err());
+        assert!(parse(&["--light", "sun,0,0,0,2,0.27"]).is_err());
+        assert!(parse(&["--light", "point,0,inf,0,20"]).is_err());
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        assert!(parse(&["--filter", "sinc"]).is_err());
+        assert!(parse(&["--samples"]).is_err());
+        assert!(parse(&["--bogus"]).is_err());
+    }
+}
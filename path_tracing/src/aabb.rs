@@ -0,0 +1,114 @@
+use super::{Point3, Ray, Vec3};
+
+/// An axis-aligned bounding box, for quickly rejecting rays that miss everything inside it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    /// The smallest box containing both points, in any order.
+    pub fn new(a: Point3, b: Point3) -> Aabb {
+        let (a, b) = (a.0 .0, b.0 .0);
+        Aabb {
+            min: Point3::new(a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])),
+            max: Point3::new(a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])),
+        }
+    }
+
+    /// The smallest box containing all the points, or None if there are none.
+    pub fn around<I: IntoIterator<Item = Point3>>(points: I) -> Option<Aabb> {
+        points
+            .into_iter()
+            .map(|p| Aabb::new(p, p))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let (a, b) = (
+            Aabb::new(self.min, other.min),
+            Aabb::new(self.max, other.max),
+        );
+        Aabb {
+            min: a.min,
+            max: b.max,
+        }
+    }
+
+    /// Grows the box by the same distance on every side.
+    pub fn expand(&self, distance: f64) -> Aabb {
+        let margin = Vec3::fill(distance);
+        Aabb {
+            min: self.min - margin,
+            max: self.max + margin,
+        }
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let (min, max) = (self.min, self.max);
+        let corner = |i: usize| {
+            Point3::new(
+                if i & 1 == 0 { min.x() } else { max.x() },
+                if i & 2 == 0 { min.y() } else { max.y() },
+                if i & 4 == 0 { min.z() } else { max.z() },
+            )
+        };
+        [0, 1, 2, 3, 4, 5, 6, 7].map(corner)
+    }
+
+    pub fn contains(&self, p: Point3) -> bool {
+        (0..3).all(|k| self.min.0 .0[k] <= p.0 .0[k] && p.0 .0[k] <= self.max.0 .0[k])
+    }
+
+    /// Whether the ray passes through the box between t_min and t_max.
+    pub fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> bool {
+        let (origin, direction) = (ray.origin().0 .0, ray.direction().0);
+        let (mut t_min, mut t_max) = (t_min, t_max);
+        for k in 0..3 {
+            let inverse = 1.0 / direction[k];
+            let mut t0 = (self.min.0 .0[k] - origin[k]) * inverse;
+            let mut t1 = (self.max.0 .0[k] - origin[k]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unions_and_corners() {
+        let a = Aabb::new(Point3::new(1.0, 0.0, 0.0), Point3::new(0.0, 1.0, 1.0));
+        assert_eq!(a.min, Point3::origin());
+        let b = Aabb::around(vec![Point3::new(2.0, -1.0, 0.5)]).unwrap();
+        let both = a.union(&b);
+        assert_eq!(both.min, Point3::new(0.0, -1.0, 0.0));
+        assert_eq!(both.max, Point3::new(2.0, 1.0, 1.0));
+        assert!(both.corners().iter().all(|&c| both.contains(c)));
+        assert_eq!(Aabb::around(a.corners().to_vec()), Some(a));
+        assert!(Aabb::around(vec![]).is_none());
+        assert_eq!(a.expand(1.0).max, Point3::new(2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn slab_test() {
+        let unit = Aabb::new(Point3::origin(), Point3::new(1.0, 1.0, 1.0));
+        let through = Ray::new(Vec3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(unit.hit(&through, 0.0, f64::MAX));
+        assert!(!unit.hit(&through, 0.0, 0.5));
+        let beside = Ray::new(Vec3::new(1.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        assert!(!unit.hit(&beside, 0.0, f64::MAX));
+        let backwards = Ray::new(Vec3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(!unit.hit(&backwards, 0.0, f64::MAX));
+    }
+}
@@ -0,0 +1,426 @@
+use super::{mix, Quat, Transform, Vec3};
+use std::ops::{Add, Mul, Sub};
+
+/// How a Track moves between its keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// Straight lines between keys, with a sudden change of speed at each key.
+    #[default]
+    Linear,
+    /// A smooth curve through every key, which can overshoot between keys that change direction.
+    CatmullRom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+}
+
+/// A value that changes over time, given by keys at some times and interpolated between them.
+/// It is held at the first and last keys outside their range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    pub interpolation: Interpolation,
+    keys: Vec<Keyframe<T>>,
+}
+
+impl<T> Default for Track<T> {
+    fn default() -> Track<T> {
+        Track {
+            interpolation: Interpolation::default(),
+            keys: Vec::new(),
+        }
+    }
+}
+
+impl<T: Copy> Track<T> {
+    pub fn new(interpolation: Interpolation) -> Track<T> {
+        Track {
+            interpolation,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Adds a key, replacing any key already at that time.
+    pub fn insert(&mut self, time: f64, value: T) {
+        let index = self.keys.partition_point(|key| key.time < time);
+        let key = Keyframe { time, value };
+        match self.keys.get_mut(index) {
+            Some(existing) if existing.time == time => *existing = key,
+            _ => self.keys.insert(index, key),
+        }
+    }
+
+    /// The keys in order of time.
+    pub fn keys(&self) -> &[Keyframe<T>] {
+        &self.keys
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The key at or before the time and how far it is towards the next key,
+    /// or None if there are no keys.
+    fn locate(&self, time: f64) -> Option<(usize, f64)> {
+        let first = self.keys.first()?;
+        if time <= first.time {
+            return Some((0, 0.0));
+        }
+        let index = self.keys.partition_point(|key| key.time <= time) - 1;
+        match self.keys.get(index + 1) {
+            Some(next) => {
+                let start = self.keys[index].time;
+                Some((index, (time - start) / (next.time - start)))
+            }
+            None => Some((index, 0.0)),
+        }
+    }
+}
+
+impl<T> Track<T>
+where
+    T: Copy + Add<T, Output = T> + Sub<T, Output = T> + Mul<f64, Output = T>,
+{
+    /// The value at the given time, or None if the track has no keys.
+    pub fn at(&self, time: f64) -> Option<T> {
+        let (index, t) = self.locate(time)?;
+        let (p1, p2) = match self.keys.get(index + 1) {
+            Some(next) if t > 0.0 => (self.keys[index], *next),
+            _ => return Some(self.keys[index].value),
+        };
+        match self.interpolation {
+            Interpolation::Linear => Some(mix(p1.value, p2.value, t)),
+            Interpolation::CatmullRom => {
+                // a Hermite curve whose tangents are the slopes through the neighbouring keys,
+                // scaled to this segment so unevenly spaced keys still join smoothly
+                let span = p2.time - p1.time;
+                let tangent = |i: usize| {
+                    let before = &self.keys[i.saturating_sub(1)];
+                    let after = &self.keys[(i + 1).min(self.keys.len() - 1)];
+                    (after.value - before.value) * (span / (after.time - before.time))
+                };
+                let (m1, m2) = (tangent(index), tangent(index + 1));
+                let (t2, t3) = (t * t, t * t * t);
+                Some(
+                    p1.value * (2.0 * t3 - 3.0 * t2 + 1.0)
+                        + m1 * (t3 - 2.0 * t2 + t)
+                        + p2.value * (3.0 * t2 - 2.0 * t3)
+                        + m2 * (t3 - t2),
+                )
+            }
+        }
+    }
+}
+
+impl Track<Quat> {
+    /// The rotation at the given time, or None if the track has no keys. Rotations always
+    /// turn at a steady rate between keys, whatever the interpolation.
+    pub fn rotation_at(&self, time: f64) -> Option<Quat> {
+        let (index, t) = self.locate(time)?;
+        let start = self.keys[index].value;
+        Some(match self.keys.get(index + 1) {
+            Some(next) if t > 0.0 => start.slerp(&next.value, t),
+            _ => start,
+        })
+    }
+}
+
+/// Keyframed translation, rotation and scale for an object. Tracks without keys
+/// leave that part of the transform alone.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TransformAnimation {
+    pub translation: Track<Vec3>,
+    pub rotation: Track<Quat>,
+    pub scale: Track<Vec3>,
+}
+
+impl TransformAnimation {
+    pub fn new(interpolation: Interpolation) -> TransformAnimation {
+        TransformAnimation {
+            translation: Track::new(interpolation),
+            rotation: Track::new(interpolation),
+            scale: Track::new(interpolation),
+        }
+    }
+
+    /// The transform at the given time, or None where the scale is zero
+    /// (or a curve overshoots through zero), so the object has no size.
+    pub fn at(&self, time: f64) -> Option<Transform> {
+        Transform::from_trs(
+            self.translation.at(time).unwrap_or_else(|| Vec3::fill(0.0)),
+            self.rotation
+                .rotation_at(time)
+                .unwrap_or_else(Quat::identity),
+            self.scale.at(time).unwrap_or_else(|| Vec3::fill(1.0)),
+        )
+    }
+}
+
+/// Keyframed settings for a perspective camera. Tracks without keys keep the still camera's value.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CameraAnimation {
+    pub origin: Track<Vec3>,
+    pub target: Track<Vec3>,
+    /// Vertical field of view in degrees.
+    pub fov: Track<f64>,
+    pub focus_distance: Track<f64>,
+}
+
+impl CameraAnimation {
+    pub fn new(interpolation: Interpolation) -> CameraAnimation {
+        CameraAnimation {
+            origin: Track::new(interpolation),
+            target: Track::new(interpolation),
+            fov: Track::new(interpolation),
+            focus_distance: Track::new(interpolation),
+        }
+    }
+}
+
+/// Keyframes for the camera and for named objects in a scene.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Animation {
+    pub camera: CameraAnimation,
+    pub objects: Vec<(String, TransformAnimation)>,
+}
+
+impl Animation {
+    /// Reads keys from text with one key per line, times in seconds:
+    ///
+    /// ```text
+    /// # comments start with a hash
+    /// interpolation catmull-rom
+    /// 0 camera origin 13 2 3
+    /// 0 camera target 0 0 0
+    /// 0 camera fov 20
+    /// 0 camera focus 10
+    /// 1.5 glass translate 0 2 0
+    /// 1.5 glass rotate 0 1 0 90
+    /// 1.5 glass scale 2
+    /// ```
+    ///
+    /// Rotations are an axis and an angle in degrees. The interpolation applies to every track.
+    /// Fields of view must be under 180 degrees and focus distances positive.
+    pub fn parse(text: &str) -> Result<Animation, String> {
+        let mut animation = Animation::default();
+        let mut interpolation = Interpolation::Linear;
+        for (number, line) in text.lines().enumerate() {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.is_empty() || words[0].starts_with('#') {
+                continue;
+            }
+            animation
+                .parse_line(&words, &mut interpolation)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        animation.set_interpolation(interpolation);
+        Ok(animation)
+    }
+
+    /// The keyframes for the named object, if it has any.
+    pub fn object(&self, name: &str) -> Option<&TransformAnimation> {
+        self.objects
+            .iter()
+            .find(|(object, _)| object == name)
+            .map(|(_, animation)| animation)
+    }
+
+    fn parse_line(
+        &mut self,
+        words: &[&str],
+        interpolation: &mut Interpolation,
+    ) -> Result<(), String> {
+        if words[0] == "interpolation" {
+            *interpolation = match words.get(1..) {
+                Some(["linear"]) => Interpolation::Linear,
+                Some(["catmull-rom"]) => Interpolation::CatmullRom,
+                _ => return Err("expected linear or catmull-rom".to_string()),
+            };
+            return Ok(());
+        }
+        let (time, target, property, values) = match words {
+            [time, target, property, values @ ..] => (*time, *target, *property, values),
+            _ => return Err("expected a time, a target, a property and values".to_string()),
+        };
+        let finite = |text: &str| text.parse::<f64>().ok().filter(|n| n.is_finite());
+        let time = finite(time).ok_or_else(|| format!("bad time {}", time))?;
+        let values = values
+            .iter()
+            .map(|v| finite(v).ok_or_else(|| format!("bad value {}", v)))
+            .collect::<Result<Vec<f64>, String>>()?;
+        let vector = || match values[..] {
+            [x, y, z] => Ok(Vec3::new(x, y, z)),
+            _ => Err(format!("{} needs three values", property)),
+        };
+        let scalar = || match values[..] {
+            [v] => Ok(v),
+            _ => Err(format!("{} needs one value", property)),
+        };
+
+        if target == "camera" {
+            let camera = &mut self.camera;
+            match property {
+                "origin" => camera.origin.insert(time, vector()?),
+                "target" => camera.target.insert(time, vector()?),
+                "fov" => match scalar()? {
+                    fov if fov > 0.0 && fov < 180.0 => camera.fov.insert(time, fov),
+                    fov => return Err(format!("fov {} is not between 0 and 180", fov)),
+                },
+                "focus" => match scalar()? {
+                    distance if distance > 0.0 => camera.focus_distance.insert(time, distance),
+                    distance => return Err(format!("focus {} is not positive", distance)),
+                },
+                _ => return Err(format!("unknown camera property {}", property)),
+            }
+            return Ok(());
+        }
+
+        let object = match self.objects.iter().position(|(name, _)| name == target) {
+            Some(index) => &mut self.objects[index].1,
+            None => {
+                self.objects
+                    .push((target.to_string(), TransformAnimation::default()));
+                &mut self.objects.last_mut().expect("just pushed").1
+            }
+        };
+        match property {
+            "translate" => object.translation.insert(time, vector()?),
+            "rotate" => match values[..] {
+                [x, y, z, degrees] => object
+                    .rotation
+                    .insert(time, Quat::from_axis_angle(Vec3::new(x, y, z), degrees)),
+                _ => return Err("rotate needs an axis and an angle".to_string()),
+            },
+            "scale" => match values[..] {
+                [s] => object.scale.insert(time, Vec3::fill(s)),
+                _ => object.scale.insert(time, vector()?),
+            },
+            _ => return Err(format!("unknown object property {}", property)),
+        }
+        Ok(())
+    }
+
+    fn set_interpolation(&mut self, interpolation: Interpolation) {
+        let camera = &mut self.camera;
+        camera.origin.interpolation = interpolation;
+        camera.target.interpolation = interpolation;
+        camera.fov.interpolation = interpolation;
+        camera.focus_distance.interpolation = interpolation;
+        for (_, object) in &mut self.objects {
+            object.translation.interpolation = interpolation;
+            object.rotation.interpolation = interpolation;
+            object.scale.interpolation = interpolation;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Point3;
+    use super::*;
+
+    fn track(interpolation: Interpolation, keys: &[(f64, f64)]) -> Track<f64> {
+        let mut track = Track::new(interpolation);
+        for &(time, value) in keys {
+            track.insert(time, value);
+        }
+        track
+    }
+
+    #[test]
+    fn linear_tracks_hold_at_the_ends() {
+        let track = track(Interpolation::Linear, &[(2.0, 10.0), (0.0, 0.0)]);
+        assert_eq!(track.keys()[0].time, 0.0);
+        assert_eq!(track.at(-1.0), Some(0.0));
+        assert_eq!(track.at(0.5), Some(2.5));
+        assert_eq!(track.at(3.0), Some(10.0));
+        assert_eq!(Track::<f64>::new(Interpolation::Linear).at(0.0), None);
+
+        let mut replaced = track.clone();
+        replaced.insert(2.0, 4.0);
+        assert_eq!(replaced.keys().len(), 2);
+        assert_eq!(replaced.at(2.0), Some(4.0));
+    }
+
+    #[test]
+    fn catmull_rom_passes_smoothly_through_keys() {
+        let keys = [(0.0, 0.0), (1.0, 1.0), (3.0, 0.0), (4.0, 2.0)];
+        let curve = track(Interpolation::CatmullRom, &keys);
+        for &(time, value) in &keys {
+            assert!((curve.at(time).unwrap() - value).abs() < 1e-12);
+        }
+        // the slope matches on both sides of an inner key even though the segments differ in length
+        let slope = |t0: f64, t1: f64| (curve.at(t1).unwrap() - curve.at(t0).unwrap()) / (t1 - t0);
+        let (before, after) = (slope(0.999, 1.0), slope(1.0, 1.001));
+        assert!((before - after).abs() < 1e-2);
+        // it overshoots a level key rather than turning sharply into it
+        let level = track(
+            Interpolation::CatmullRom,
+            &[(0.0, 0.0), (1.0, 1.0), (2.0, 1.0)],
+        );
+        assert!(level.at(1.5).unwrap() > 1.0);
+        // evenly spaced keys on a line stay on the line
+        let line = track(
+            Interpolation::CatmullRom,
+            &[(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)],
+        );
+        assert!((line.at(1.5).unwrap() - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn transforms_follow_their_tracks() {
+        let mut animation = TransformAnimation::new(Interpolation::Linear);
+        assert_eq!(
+            animation.at(1.0).unwrap().point(Point3::origin()),
+            Point3::origin()
+        );
+        animation.translation.insert(0.0, Vec3::fill(0.0));
+        animation.translation.insert(2.0, Vec3::new(4.0, 0.0, 0.0));
+        animation.rotation.insert(0.0, Quat::identity());
+        animation
+            .rotation
+            .insert(2.0, Quat::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 180.0));
+        let p = animation.at(1.0).unwrap().point(Point3::new(1.0, 0.0, 0.0));
+        // a quarter turn about y takes +x to -z, then the translation moves it halfway
+        assert!((p - Point3::new(2.0, 0.0, -1.0)).length() < 1e-12);
+    }
+
+    #[test]
+    fn parses_camera_and_object_keys() {
+        let text = "# a short move\n\
+                    interpolation catmull-rom\n\
+                    0 camera origin 13 2 3\n\
+                    1 camera origin 10 2 3\n\
+                    0 camera fov 20\n\
+                    0.5 glass scale 2\n\
+                    0.5 glass rotate 0 1 0 90\n";
+        let animation = Animation::parse(text).unwrap();
+        assert_eq!(
+            animation.camera.origin.interpolation,
+            Interpolation::CatmullRom
+        );
+        assert_eq!(
+            animation.camera.origin.at(1.0),
+            Some(Vec3::new(10.0, 2.0, 3.0))
+        );
+        assert_eq!(animation.camera.fov.at(3.0), Some(20.0));
+        assert!(animation.camera.target.is_empty());
+        let glass = animation.object("glass").unwrap();
+        assert_eq!(glass.scale.at(0.0), Some(Vec3::fill(2.0)));
+        assert_eq!(glass.rotation.interpolation, Interpolation::CatmullRom);
+        assert!(animation.object("metal").is_none());
+
+        assert!(Animation::parse("0 camera origin 1 2").is_err());
+        assert!(Animation::parse("0 camera zoom 2").is_err());
+        assert!(Animation::parse("0 camera fov 180").is_err());
+        assert!(Animation::parse("0 camera fov 0").is_err());
+        assert!(Animation::parse("0 camera focus -1").is_err());
+        assert!(Animation::parse("inf glass scale 1").is_err());
+        assert!(Animation::parse("NaN camera fov 20").is_err());
+        assert!(Animation::parse("0 glass translate 0 nan 0").is_err());
+        let error = Animation::parse("\nx glass scale 1").unwrap_err();
+        assert!(error.starts_with("line 2"));
+    }
+}
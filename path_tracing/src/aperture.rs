@@ -0,0 +1,139 @@
+use super::{rand, random_in_unit_disk};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// The shape of the lens opening. Out of focus highlights take this shape, so
+/// a six bladed aperture gives hexagonal bokeh.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Aperture {
+    #[default]
+    Circle,
+    /// A regular polygon formed by the given number of blades, at least three,
+    /// turned counter-clockwise by rotation degrees.
+    Polygon { blades: u32, rotation: f64 },
+    /// An arbitrary shape, with light passing through in proportion to the mask.
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    /// Returns a random point in the opening, scaled to fit the unit circle
+    /// (or for masks, the square around it).
+    pub fn sample(&self) -> [f64; 2] {
+        match self {
+            Aperture::Circle => random_in_unit_disk(),
+            Aperture::Polygon { blades, rotation } => {
+                // pick one of the equal triangles between the center and an edge, then a point in it
+                let blades = *blades as f64;
+                let edge = (rand() * blades).floor();
+                let corner = |i: f64| {
+                    let angle = rotation.to_radians() + 2.0 * PI * i / blades;
+                    [angle.cos(), angle.sin()]
+                };
+                let (a, b) = (corner(edge), corner(edge + 1.0));
+                let (mut s, mut t) = (rand(), rand());
+                if s + t > 1.0 {
+                    s = 1.0 - s;
+                    t = 1.0 - t;
+                }
+                [a[0] * s + b[0] * t, a[1] * s + b[1] * t]
+            }
+            Aperture::Mask(mask) => mask.sample(),
+        }
+    }
+}
+
+/// A grayscale image of the aperture, where 0 blocks light and 1 lets it all through.
+/// The image covers the square around the lens opening.
+#[derive(Debug, PartialEq)]
+pub struct ApertureMask {
+    width: u32,
+    height: u32,
+    /// Running totals of the pixel values, normalized to end at 1, for picking pixels
+    /// in proportion to how much light they let through.
+    cumulative: Vec<f64>,
+}
+
+impl ApertureMask {
+    /// Builds a mask from pixel values given row by row from the top left.
+    /// Returns None if the number of values doesn't match the size or no light gets through.
+    pub fn new(width: u32, height: u32, values: &[f64]) -> Option<ApertureMask> {
+        if (width as usize).checked_mul(height as usize) != Some(values.len()) {
+            return None;
+        }
+        let mut total = 0.0;
+        let mut cumulative: Vec<f64> = values
+            .iter()
+            .map(|value| {
+                total += value.max(0.0);
+                total
+            })
+            .collect();
+        if total <= 0.0 {
+            return None;
+        }
+        for c in &mut cumulative {
+            *c /= total;
+        }
+        Some(ApertureMask {
+            width,
+            height,
+            cumulative,
+        })
+    }
+
+    pub fn sample(&self) -> [f64; 2] {
+        let r = rand();
+        let index = self
+            .cumulative
+            .partition_point(|&c| c <= r)
+            .min(self.cumulative.len() - 1);
+        let width = self.width as usize;
+        let (x, y) = (index % width, index / width);
+        [
+            2.0 * (x as f64 + rand()) / self.width as f64 - 1.0,
+            1.0 - 2.0 * (y as f64 + rand()) / self.height as f64,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::seed_random;
+    use super::*;
+
+    #[test]
+    fn polygon_samples_stay_inside() {
+        seed_random(1);
+        let square = Aperture::Polygon {
+            blades: 4,
+            rotation: 45.0,
+        };
+        let limit = f64::sqrt(0.5) + 1e-12;
+        let mut corners = [false; 4];
+        for _ in 0..1000 {
+            let [x, y] = square.sample();
+            assert!(x.abs() <= limit && y.abs() <= limit);
+            corners[((x > 0.0) as usize) * 2 + (y > 0.0) as usize] = true;
+        }
+        assert!(corners.iter().all(|&seen| seen));
+    }
+
+    #[test]
+    fn masks_only_pass_light_where_open() {
+        seed_random(2);
+        // a 2x2 mask open in the top right quarter only
+        let mask = ApertureMask::new(2, 2, &[0.0, 1.0, 0.0, 0.0]).unwrap();
+        for _ in 0..100 {
+            let [x, y] = mask.sample();
+            assert!((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y));
+        }
+        assert!(ApertureMask::new(2, 2, &[0.0; 4]).is_none());
+        assert!(ApertureMask::new(2, 2, &[1.0; 3]).is_none());
+    }
+
+    #[test]
+    fn mask_sizes_that_overflow_are_refused() {
+        // 65536 * 65537 wraps around to 65536 in 32 bits
+        assert!(ApertureMask::new(65536, 65537, &[1.0; 65536]).is_none());
+    }
+}
@@ -1,56 +1,680 @@
-use super::{cross, normalize, random_in_unit_disk, Ray, Vec3};
+use super::{cross, dot, normalize, rand, Aperture, Hitable, Point3, Quat, Ray, Vec3, World};
+use std::f64::consts::PI;
+use std::fmt::Debug;
 
-#[derive(Debug)]
-pub struct Camera {
-    pub origin: Vec3,
-    pub lower_left_corner: Vec3,
+/// Cameras turn positions on the image into rays to trace.
+pub trait Camera: Debug + Send + Sync {
+    /// The ray through the image at (u, v), where both run from 0 to 1
+    /// starting at the bottom left corner. None where the camera sees nothing,
+    /// such as outside a fisheye's image circle, which renders black.
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray>;
+}
+
+/// A perspective camera with a thin lens, so things away from the focus distance are blurred.
+#[derive(Debug, Clone)]
+pub struct PerspectiveCamera {
+    pub origin: Point3,
+    pub lower_left_corner: Point3,
     pub horizontal: Vec3,
     pub vertical: Vec3,
     pub lens_radius: f64,
+    pub aperture: Aperture,
     pub u: Vec3,
     pub v: Vec3,
+    /// The shutter is open from shutter_open until shutter_close. Rays are spread evenly
+    /// over that time, so anything that moves in between is blurred.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+    /// A point on the plane in focus and its normal, when a tilted lens turns it away from
+    /// the image plane. None when everything at the focus distance is sharp.
+    pub focal_plane: Option<(Point3, Vec3)>,
 }
 
-impl Camera {
-    pub fn new(
-        origin: Vec3,
-        target: Vec3,
+impl PerspectiveCamera {
+    pub fn new<P: Into<Point3>>(
+        origin: P,
+        target: P,
         up: Vec3,
         fov: f64,
         aspect: f64,
         aperture: f64,
         focus_dist: f64,
-    ) -> Camera {
-        let theta = fov * std::f64::consts::PI / 180.0;
+    ) -> PerspectiveCamera {
+        let theta = fov * PI / 180.0;
         let half_height = f64::tan(theta / 2.0);
         let half_width = aspect * half_height;
-        let w = normalize(origin - target);
-        let u = normalize(cross(up, w));
-        let v = cross(w, u);
+        let (origin, target) = (origin.into(), target.into());
+        let (u, v, w) = look_basis(origin, target, up);
 
         let lower_left_corner =
             origin - u * half_width * focus_dist - v * half_height * focus_dist - w * focus_dist;
         let horizontal = u * 2.0 * focus_dist * half_width;
         let vertical = v * 2.0 * focus_dist * half_height;
 
-        Camera {
+        PerspectiveCamera {
             origin,
             lower_left_corner,
             horizontal,
             vertical,
             lens_radius: aperture / 2.0,
+            aperture: Aperture::Circle,
             u,
             v,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+            focal_plane: None,
+        }
+    }
+
+    pub fn with_shutter(self, open: f64, close: f64) -> PerspectiveCamera {
+        PerspectiveCamera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    /// Gives the lens opening a different shape, keeping its radius.
+    pub fn with_aperture(self, aperture: Aperture) -> PerspectiveCamera {
+        PerspectiveCamera { aperture, ..self }
+    }
+
+    /// Shifts the lens sideways by x image widths and up by y image heights, moving the
+    /// view without turning the camera. Keeping the camera level and shifting up keeps
+    /// the verticals of buildings parallel.
+    pub fn with_shift(self, x: f64, y: f64) -> PerspectiveCamera {
+        PerspectiveCamera {
+            lower_left_corner: self.lower_left_corner + self.horizontal * x + self.vertical * y,
+            ..self
         }
     }
 
-    pub fn make_ray(&self, u: f64, v: f64) -> Ray {
-        let rd = random_in_unit_disk();
+    /// Tilts the plane in focus by tilt degrees about the horizontal axis, leaning its top
+    /// away from the camera, and swings it by swing degrees about the vertical, turning its
+    /// right side away. It pivots where the view axis meets the focus distance.
+    /// Tilting the focus across a scene is what makes it look like a miniature.
+    pub fn with_tilt(self, tilt: f64, swing: f64) -> PerspectiveCamera {
+        let w = cross(self.u, self.v);
+        let pivot = self.origin - w * self.focus_distance();
+        self.with_tilt_through(tilt, swing, pivot)
+    }
+
+    /// Tilts and swings the plane in focus like with_tilt, but pivots it about a point,
+    /// e.g. one found by autofocus_point, so that point stays sharp.
+    pub fn with_tilt_through(self, tilt: f64, swing: f64, pivot: Point3) -> PerspectiveCamera {
+        let w = cross(self.u, self.v);
+        let normal = Quat::from_axis_angle(self.u, -tilt).rotate(w);
+        let normal = Quat::from_axis_angle(self.v, swing).rotate(normal);
+        PerspectiveCamera {
+            focal_plane: Some((pivot, normal)),
+            ..self
+        }
+    }
+
+    /// How far in front of the camera things are sharp, along the view direction.
+    pub fn focus_distance(&self) -> f64 {
+        dot(self.origin - self.lower_left_corner, cross(self.u, self.v))
+    }
+
+    /// Moves the focus to a new distance, keeping the field of view, shift and tilt.
+    pub fn with_focus_distance(self, focus_dist: f64) -> PerspectiveCamera {
+        let scale = focus_dist / self.focus_distance();
+        let origin = self.origin;
+        PerspectiveCamera {
+            lower_left_corner: origin + (self.lower_left_corner - origin) * scale,
+            horizontal: self.horizontal * scale,
+            vertical: self.vertical * scale,
+            focal_plane: self
+                .focal_plane
+                .map(|(point, normal)| (origin + (point - origin) * scale, normal)),
+            ..self
+        }
+    }
+
+    /// Finds the distance to whatever is seen through the center of the lens at (u, v)
+    /// on the image, for focusing on it. None if nothing in the world is there.
+    pub fn autofocus(&self, world: &World, u: f64, v: f64) -> Option<f64> {
+        Some(self.depth(self.autofocus_point(world, u, v)?))
+    }
+
+    /// How far in front of the camera a point is, along the view direction.
+    pub fn depth(&self, point: Point3) -> f64 {
+        dot(point - self.origin, -cross(self.u, self.v))
+    }
+
+    /// Finds the point seen through the center of the lens at (u, v) on the image.
+    /// None if nothing in the world is there.
+    pub fn autofocus_point(&self, world: &World, u: f64, v: f64) -> Option<Point3> {
+        let pinhole = self.lower_left_corner + self.horizontal * u + self.vertical * v;
+        let ray = Ray::at_time(self.origin, pinhole - self.origin, self.shutter_open);
+        let hit = world.hit(&ray, 0.001, f64::MAX)?;
+        Some(*hit.position())
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let rd = self.aperture.sample();
         let offset = (self.u * rd[0] + self.v * rd[1]) * self.lens_radius;
         let origin = self.origin + offset;
-        Ray::new(
+        let time = shutter_time(self.shutter_open, self.shutter_close);
+        let pinhole = self.lower_left_corner + self.horizontal * u + self.vertical * v;
+        let direction = match self.focal_plane {
+            None => pinhole - origin,
+            Some((point, normal)) => {
+                // focus where the ray through the center of the lens meets the plane,
+                // or at infinity if it never does
+                let through = pinhole - self.origin;
+                let t = dot(point - self.origin, normal) / dot(through, normal);
+                if t > 0.0 && t.is_finite() {
+                    self.origin + through * t - origin
+                } else {
+                    through
+                }
+            }
+        };
+        Some(Ray::at_time(origin, direction, time))
+    }
+}
+
+/// An orthographic camera, where all rays are parallel so there is no perspective:
+/// parallel lines stay parallel and things keep their size at any distance.
+#[derive(Debug, Clone)]
+pub struct OrthographicCamera {
+    pub lower_left_corner: Point3,
+    pub horizontal: Vec3,
+    pub vertical: Vec3,
+    pub direction: Vec3,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl OrthographicCamera {
+    /// Looks from origin towards target, seeing a view_width by view_height rectangle
+    /// of the scene centered on the line between them.
+    pub fn new<P: Into<Point3>>(
+        origin: P,
+        target: P,
+        up: Vec3,
+        view_width: f64,
+        view_height: f64,
+    ) -> OrthographicCamera {
+        let (origin, target) = (origin.into(), target.into());
+        let (u, v, w) = look_basis(origin, target, up);
+
+        let horizontal = u * view_width;
+        let vertical = v * view_height;
+        OrthographicCamera {
+            lower_left_corner: origin - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: -w,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    pub fn with_shutter(self, open: f64, close: f64) -> OrthographicCamera {
+        OrthographicCamera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        Some(Ray::at_time(
+            self.lower_left_corner + self.horizontal * u + self.vertical * v,
+            self.direction,
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
+    }
+}
+
+/// A panoramic camera seeing in every direction. The image is an equirectangular map:
+/// longitude runs across it, starting and ending behind the camera with target in the
+/// middle, and latitude runs from straight down at the bottom to straight up at the top.
+#[derive(Debug, Clone)]
+pub struct EquirectangularCamera {
+    pub origin: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new<P: Into<Point3>>(origin: P, target: P, up: Vec3) -> EquirectangularCamera {
+        let (origin, target) = (origin.into(), target.into());
+        let (u, v, w) = look_basis(origin, target, up);
+        EquirectangularCamera {
             origin,
-            self.lower_left_corner + self.horizontal * u + self.vertical * v - origin,
-        )
+            u,
+            v,
+            w,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    pub fn with_shutter(self, open: f64, close: f64) -> EquirectangularCamera {
+        EquirectangularCamera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    /// The unit direction seen at (u, v) on the image.
+    pub fn direction(&self, u: f64, v: f64) -> Vec3 {
+        let longitude = (u - 0.5) * 2.0 * PI;
+        let latitude = (v - 0.5) * PI;
+        let around = self.u * longitude.sin() - self.w * longitude.cos();
+        around * latitude.cos() + self.v * latitude.sin()
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        Some(Ray::at_time(
+            self.origin,
+            self.direction(u, v),
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
+    }
+}
+
+/// A panoramic camera rendering the six faces of a cube around it, side by side in a strip
+/// in the order of CubeMapCamera::FACES. Each face is a square 90 degree perspective view,
+/// so the image should be six times as wide as it is high.
+#[derive(Debug, Clone)]
+pub struct CubeMapCamera {
+    pub origin: Point3,
+    /// The forward, right and up directions of each face.
+    pub faces: [(Vec3, Vec3, Vec3); 6],
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl CubeMapCamera {
+    /// Names of the faces from left to right, by the camera axis each one looks along:
+    /// x is to the right, y is up and -z is towards the target.
+    pub const FACES: [&'static str; 6] = ["px", "nx", "py", "ny", "pz", "nz"];
+
+    pub fn new<P: Into<Point3>>(origin: P, target: P, up: Vec3) -> CubeMapCamera {
+        let (origin, target) = (origin.into(), target.into());
+        let (u, v, w) = look_basis(origin, target, up);
+        let face = |forward: Vec3, up: Vec3| (forward, cross(forward, up), up);
+        CubeMapCamera {
+            origin,
+            faces: [
+                face(u, v),
+                face(-u, v),
+                face(v, w),
+                face(-v, -w),
+                face(w, v),
+                face(-w, v),
+            ],
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    pub fn with_shutter(self, open: f64, close: f64) -> CubeMapCamera {
+        CubeMapCamera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+}
+
+impl Camera for CubeMapCamera {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let face = (u * 6.0).floor().clamp(0.0, 5.0);
+        let (forward, right, up) = self.faces[face as usize];
+        let across = u * 6.0 - face;
+        Some(Ray::at_time(
+            self.origin,
+            forward + right * (2.0 * across - 1.0) + up * (2.0 * v - 1.0),
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
+    }
+}
+
+/// How a fisheye lens spreads angles from its axis over the image circle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FisheyeMapping {
+    /// Distance from the center is proportional to the angle, so angles are kept evenly
+    /// spaced. Common for measurement and dome projection.
+    Equidistant,
+    /// Equal areas on the image cover equal solid angles, like most real fisheye lenses.
+    Equisolid,
+    /// Keeps shapes in the middle of the image but stretches towards the edge, growing
+    /// without limit at 360 degrees, so its field of view must be less than that.
+    Stereographic,
+}
+
+impl FisheyeMapping {
+    /// The angle from the axis at distance r from the center of the image circle,
+    /// where r = 1 is the edge and the edge is at max_angle.
+    pub fn angle(&self, r: f64, max_angle: f64) -> f64 {
+        match self {
+            FisheyeMapping::Equidistant => r * max_angle,
+            FisheyeMapping::Equisolid => 2.0 * f64::asin(r * f64::sin(max_angle / 2.0)),
+            FisheyeMapping::Stereographic => 2.0 * f64::atan(r * f64::tan(max_angle / 2.0)),
+        }
+    }
+}
+
+/// A fisheye camera seeing fov degrees across its image circle, up to 360.
+/// The circle fills the shorter side of the image and anything outside it is black.
+#[derive(Debug, Clone)]
+pub struct FisheyeCamera {
+    pub origin: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+    pub fov: f64,
+    pub aspect: f64,
+    pub mapping: FisheyeMapping,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new<P: Into<Point3>>(
+        origin: P,
+        target: P,
+        up: Vec3,
+        fov: f64,
+        aspect: f64,
+        mapping: FisheyeMapping,
+    ) -> FisheyeCamera {
+        let (origin, target) = (origin.into(), target.into());
+        let (u, v, w) = look_basis(origin, target, up);
+        FisheyeCamera {
+            origin,
+            u,
+            v,
+            w,
+            fov,
+            aspect,
+            mapping,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    pub fn with_shutter(self, open: f64, close: f64) -> FisheyeCamera {
+        FisheyeCamera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    /// The unit direction seen at (u, v) on the image, or None outside the image circle.
+    pub fn direction(&self, u: f64, v: f64) -> Option<Vec3> {
+        let (mut x, mut y) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        if self.aspect > 1.0 {
+            x *= self.aspect;
+        } else {
+            y /= self.aspect;
+        }
+        let r = f64::sqrt(x * x + y * y);
+        if r > 1.0 {
+            return None;
+        }
+        let theta = self.mapping.angle(r, self.fov * PI / 360.0);
+        let phi = f64::atan2(y, x);
+        let sideways = (self.u * phi.cos() + self.v * phi.sin()) * theta.sin();
+        Some(sideways - self.w * theta.cos())
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let direction = self.direction(u, v)?;
+        Some(Ray::at_time(
+            self.origin,
+            direction,
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
+    }
+}
+
+/// The right, up and backwards unit vectors of a camera at origin looking at target.
+pub(crate) fn look_basis(origin: Point3, target: Point3, up: Vec3) -> (Vec3, Vec3, Vec3) {
+    let w = normalize(origin - target);
+    let u = normalize(cross(up, w));
+    let v = cross(w, u);
+    (u, v, w)
+}
+
+/// Picks a moment while the shutter is open. No random number is drawn when it opens
+/// and closes at once, so renders without motion blur don't change.
+pub(crate) fn shutter_time(open: f64, close: f64) -> f64 {
+    if close > open {
+        open + rand() * (close - open)
+    } else {
+        open
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Color, Lambertian, Sphere};
+    use super::*;
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = OrthographicCamera::new(
+            Point3::new(0.0, 0.0, 5.0),
+            Point3::origin(),
+            Vec3::new(0.0, 1.0, 0.0),
+            4.0,
+            2.0,
+        );
+        let corner = camera.make_ray(0.0, 0.0).unwrap();
+        assert_eq!(*corner.origin(), Point3::new(-2.0, -1.0, 5.0));
+        let center = camera.make_ray(0.5, 0.5).unwrap();
+        assert_eq!(*center.origin(), Point3::new(0.0, 0.0, 5.0));
+        assert_eq!(*corner.direction(), *center.direction());
+        assert_eq!(*center.direction(), Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn equirectangular_covers_the_sphere() {
+        let camera = EquirectangularCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert_near(camera.direction(0.5, 0.5), Vec3::new(0.0, 0.0, -1.0));
+        assert_near(camera.direction(0.75, 0.5), Vec3::new(1.0, 0.0, 0.0));
+        assert_near(camera.direction(0.0, 0.5), Vec3::new(0.0, 0.0, 1.0));
+        assert_near(camera.direction(0.3, 1.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_near(
+            *camera.make_ray(0.5, 0.0).unwrap().direction(),
+            Vec3::new(0.0, -1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn cube_map_faces_look_along_the_axes() {
+        let camera = CubeMapCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let axes = [
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        ];
+        for (face, axis) in axes.iter().enumerate() {
+            let center = camera.make_ray((face as f64 + 0.5) / 6.0, 0.5).unwrap();
+            assert_near(*center.direction(), *axis);
+        }
+        // the front face matches a 90 degree perspective camera
+        let corner = camera.make_ray(5.0 / 6.0, 0.0).unwrap();
+        assert_near(*corner.direction(), Vec3::new(-1.0, -1.0, -1.0));
+        let corner = camera.make_ray(1.0, 1.0).unwrap();
+        assert_near(*corner.direction(), Vec3::new(1.0, 1.0, -1.0));
+    }
+
+    #[test]
+    fn fisheye_mappings() {
+        let fisheye = |fov, mapping| {
+            FisheyeCamera::new(
+                Point3::origin(),
+                Point3::new(0.0, 0.0, -1.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                fov,
+                2.0,
+                mapping,
+            )
+        };
+        let mappings = [
+            FisheyeMapping::Equidistant,
+            FisheyeMapping::Equisolid,
+            FisheyeMapping::Stereographic,
+        ];
+        for &mapping in &mappings {
+            let camera = fisheye(180.0, mapping);
+            assert_near(
+                camera.direction(0.5, 0.5).unwrap(),
+                Vec3::new(0.0, 0.0, -1.0),
+            );
+            // the edge of the circle is at the top of the image and halfway to the sides
+            assert_near(
+                camera.direction(0.5, 1.0).unwrap(),
+                Vec3::new(0.0, 1.0, 0.0),
+            );
+            assert_near(
+                camera.direction(0.75, 0.5).unwrap(),
+                Vec3::new(1.0, 0.0, 0.0),
+            );
+            assert!(camera.make_ray(0.9, 0.5).is_none());
+            assert!(camera.make_ray(0.0, 0.0).is_none());
+        }
+
+        // halfway to the edge the mappings differ
+        let halfway = |mapping| fisheye(180.0, mapping).direction(0.625, 0.5).unwrap();
+        let angle = |d: Vec3| f64::acos(-d.z()).to_degrees();
+        assert!((angle(halfway(FisheyeMapping::Equidistant)) - 45.0).abs() < 1e-9);
+        assert!(angle(halfway(FisheyeMapping::Equisolid)) < 45.0);
+        assert!(angle(halfway(FisheyeMapping::Stereographic)) > 45.0);
+
+        let all_round = fisheye(360.0, FisheyeMapping::Equidistant);
+        assert_near(
+            all_round.direction(0.5, 1.0).unwrap(),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn shifted_and_tilted_lenses() {
+        let camera = PerspectiveCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            1.0,
+            2.0,
+        );
+        // shifting up looks higher without turning the camera
+        let shifted = camera.clone().with_shift(0.0, 0.25);
+        assert_near(shifted.lower_left_corner.0, Vec3::new(-2.0, -1.0, -2.0));
+        assert_near(shifted.u, camera.u);
+
+        // with the focal plane tilted back 45 degrees, rays through the top of the image
+        // all meet further away than those through the bottom
+        let tilted = camera.with_tilt(45.0, 0.0);
+        let focus = |v| {
+            let rays: Vec<Ray> = (0..20).map(|_| tilted.make_ray(0.5, v).unwrap()).collect();
+            // the rays all pass through the point where the center ray crosses the plane
+            let (point, normal) = tilted.focal_plane.unwrap();
+            let meet = |ray: &Ray| {
+                let t = dot(point - *ray.origin(), normal) / dot(*ray.direction(), normal);
+                ray.point_at_parameter(t)
+            };
+            let first = meet(&rays[0]);
+            for ray in &rays {
+                assert_near(meet(ray).0, first.0);
+            }
+            -first.z()
+        };
+        assert_near(Vec3::fill(focus(0.5)), Vec3::fill(2.0));
+        assert!(focus(0.9) > focus(0.5) && focus(0.5) > focus(0.1));
+
+        let swung = tilted.with_tilt(0.0, 30.0);
+        let (point, normal) = swung.focal_plane.unwrap();
+        // the right side of the plane is further away
+        let right = point + Vec3::new(1.0, 0.0, -normal.x() / normal.z());
+        assert!(right.z() < point.z());
+        assert!(normal.x() > 0.0 && normal.y().abs() < 1e-12);
+    }
+
+    #[test]
+    fn autofocus_on_a_sphere() {
+        let mut world = World::new();
+        world.push(Box::new(Sphere {
+            center: Point3::new(0.0, 0.0, -4.0),
+            radius: 1.0,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        }));
+        let camera = PerspectiveCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.5,
+            10.0,
+        );
+        assert_near(Vec3::fill(camera.focus_distance()), Vec3::fill(10.0));
+        // the center of the image sees the front of the sphere, 3 away
+        let distance = camera.autofocus(&world, 0.5, 0.5).unwrap();
+        assert_near(Vec3::fill(distance), Vec3::fill(3.0));
+        assert!(camera.autofocus(&world, 0.0, 1.0).is_none());
+
+        let focused = camera.clone().with_focus_distance(distance);
+        assert_near(Vec3::fill(focused.focus_distance()), Vec3::fill(3.0));
+        // the view doesn't change, only where it's sharp
+        let through = |camera: &PerspectiveCamera| {
+            normalize(camera.lower_left_corner + camera.horizontal * 0.2 - camera.origin)
+        };
+        assert_near(through(&focused), through(&camera));
+    }
+
+    #[test]
+    fn perspective_rays_spread_from_the_eye() {
+        let camera = PerspectiveCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            2.0,
+            0.0,
+            1.0,
+        );
+        let corner = camera.make_ray(0.0, 0.0).unwrap();
+        assert_eq!(*corner.origin(), Point3::origin());
+        assert_near(*corner.direction(), Vec3::new(-2.0, -1.0, -1.0));
     }
 }
@@ -0,0 +1,100 @@
+use super::film::{read_u32, read_u64, write_u32, write_u64};
+use super::{Film, Tile};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"PTCK";
+const VERSION: u32 = 2;
+// far longer than any filter's description, but small enough to reject garbage quickly
+const LONGEST_FILTER: u32 = 1024;
+
+/// A snapshot of a progressive render that can be written to disk and resumed later.
+/// Sampling is seeded from the render seed and pass number, so the seed and pass count
+/// are all the random state needed to continue exactly where the render left off.
+/// The filter and region are recorded so a render can't resume with different ones.
+pub struct Checkpoint {
+    pub seed: u64,
+    pub passes: u32,
+    pub samples_per_pixel: u32,
+    /// The Debug form of the filter the samples were splatted with.
+    pub filter: String,
+    pub region: Tile,
+    pub film: Film,
+}
+
+impl Checkpoint {
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        write_u32(out, VERSION)?;
+        write_u64(out, self.seed)?;
+        write_u32(out, self.passes)?;
+        write_u32(out, self.samples_per_pixel)?;
+        write_u32(out, self.filter.len() as u32)?;
+        out.write_all(self.filter.as_bytes())?;
+        let region = &self.region;
+        for value in &[region.x, region.y, region.width, region.height] {
+            write_u32(out, *value)?;
+        }
+        self.film.write_to(out)
+    }
+
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<Checkpoint> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(input)? != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a render checkpoint",
+            ));
+        }
+
+        let seed = read_u64(input)?;
+        let passes = read_u32(input)?;
+        let samples_per_pixel = read_u32(input)?;
+        let filter_len = read_u32(input)?;
+        if filter_len > LONGEST_FILTER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Checkpoint filter description is too long",
+            ));
+        }
+        let mut filter = vec![0; filter_len as usize];
+        input.read_exact(&mut filter)?;
+        let filter =
+            String::from_utf8(filter).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let region = Tile {
+            x: read_u32(input)?,
+            y: read_u32(input)?,
+            width: read_u32(input)?,
+            height: read_u32(input)?,
+        };
+
+        Ok(Checkpoint {
+            seed,
+            passes,
+            samples_per_pixel,
+            filter,
+            region,
+            film: Film::read_from(input)?,
+        })
+    }
+
+    /// Writes the checkpoint to a file.
+    /// The file is replaced only once the new checkpoint is complete,
+    /// so a render killed mid-write leaves the previous checkpoint intact.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+
+        let mut out = BufWriter::new(File::create(&partial)?);
+        self.write_to(&mut out)?;
+        out.into_inner()?.sync_all()?;
+        std::fs::rename(&partial, path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Checkpoint> {
+        Checkpoint::read_from(&mut BufReader::new(File::open(path)?))
+    }
+}
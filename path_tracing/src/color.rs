@@ -0,0 +1,124 @@
+use super::Vec3;
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Sub};
+
+/// A linear RGB color, or a per-channel factor such as an albedo.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Color(pub Vec3);
+
+impl Color {
+    pub fn new(r: f64, g: f64, b: f64) -> Color {
+        Color(Vec3::new(r, g, b))
+    }
+
+    pub fn fill(v: f64) -> Color {
+        Color(Vec3::fill(v))
+    }
+
+    pub fn black() -> Color {
+        Color::fill(0.0)
+    }
+
+    pub fn r(&self) -> f64 {
+        self.0 .0[0]
+    }
+
+    pub fn g(&self) -> f64 {
+        self.0 .0[1]
+    }
+
+    pub fn b(&self) -> f64 {
+        self.0 .0[2]
+    }
+
+    pub fn channels(&self) -> [f64; 3] {
+        self.0 .0
+    }
+
+    /// Applies a function to each channel.
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Color {
+        Color(Vec3(self.0 .0.map(f)))
+    }
+}
+
+impl From<Vec3> for Color {
+    fn from(v: Vec3) -> Color {
+        Color(v)
+    }
+}
+
+impl From<Color> for Vec3 {
+    fn from(c: Color) -> Vec3 {
+        c.0
+    }
+}
+
+impl Add for Color {
+    type Output = Color;
+
+    fn add(self, rhs: Color) -> Color {
+        Color(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for Color {
+    fn add_assign(&mut self, rhs: Color) {
+        self.0 += rhs.0;
+    }
+}
+
+impl Sub for Color {
+    type Output = Color;
+
+    fn sub(self, rhs: Color) -> Color {
+        Color(self.0 - rhs.0)
+    }
+}
+
+/// Filters one color by another, channel by channel.
+impl Mul for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Color {
+        Color(self.0 * rhs.0)
+    }
+}
+
+impl MulAssign for Color {
+    fn mul_assign(&mut self, rhs: Color) {
+        self.0 *= rhs.0;
+    }
+}
+
+impl Mul<f64> for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: f64) -> Color {
+        Color(self.0 * rhs)
+    }
+}
+
+impl Div<f64> for Color {
+    type Output = Color;
+
+    fn div(self, rhs: f64) -> Color {
+        Color(self.0 / rhs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::mix;
+    use super::*;
+
+    #[test]
+    fn channel_arithmetic() {
+        let sky = Color::new(0.5, 0.7, 1.0);
+        let albedo = Color::new(0.5, 0.5, 0.0);
+        assert_eq!(sky * albedo, Color::new(0.25, 0.35, 0.0));
+        assert_eq!(sky * 2.0, Color::new(1.0, 1.4, 2.0));
+        assert_eq!((sky + albedo) / 2.0, Color::new(0.5, 0.6, 0.5));
+        assert_eq!(mix(Color::black(), sky, 0.5), Color::new(0.25, 0.35, 0.5));
+        assert_eq!(sky.map(|c| c * c).b(), 1.0);
+        assert_eq!(sky.channels(), [0.5, 0.7, 1.0]);
+    }
+}
@@ -0,0 +1,517 @@
+//! Rendering across processes: a coordinator hands tiles of each pass to workers
+//! connected over TCP and accumulates the films they send back.
+//!
+//! Every worker renders from the same scene description, a list of arguments the
+//! coordinator sends when the worker connects. Samples are seeded by pass and pixel, so
+//! the combined image matches a render made in a single process.
+
+use super::film::{read_u32, write_u32};
+use super::{Film, ProgressiveRenderer, Tile};
+use std::collections::VecDeque;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const MAGIC: &[u8; 4] = b"PTDR";
+const VERSION: u32 = 2;
+const JOB: u8 = 1;
+const DONE: u8 = 0;
+/// Scene descriptions are command lines, so anything longer in total is not from a
+/// coordinator.
+const LONGEST_SCENE: u32 = 1 << 20;
+
+/// A tile of one pass, handed to a worker to render.
+#[derive(Debug, Clone, Copy)]
+struct Job {
+    id: usize,
+    pass: u32,
+    samples: u32,
+    tile: Tile,
+    /// The pixels the film rendered for the tile should cover.
+    bounds: Tile,
+}
+
+/// Work shared between the connections of a coordinator.
+struct Queue {
+    pending: VecDeque<Job>,
+    results: Vec<Option<Film>>,
+    remaining: usize,
+    /// The width and height of the image the films belong to.
+    size: (u32, u32),
+}
+
+/// Listens for workers and distributes render passes between them.
+pub struct Coordinator {
+    listener: TcpListener,
+    scene: Vec<String>,
+    timeout: Duration,
+}
+
+impl Coordinator {
+    /// Listens on the given address. Every worker that connects is sent the scene description.
+    /// Each argument is sent as it is, so arguments may contain spaces.
+    pub fn bind<A: ToSocketAddrs>(address: A, scene: &[String]) -> io::Result<Coordinator> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Coordinator {
+            listener,
+            scene: scene.to_vec(),
+            timeout: Duration::from_secs(600),
+        })
+    }
+
+    /// Sets how long a worker may take to answer before its tile is handed to another.
+    /// Rendering one tile has to fit within it. The default is ten minutes.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub fn local_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Renders passes taking the given numbers of samples per pixel with connected workers,
+    /// and adds them to the renderer.
+    /// Workers may connect at any time; tiles held by a worker that disconnects or stops
+    /// answering are handed to another. Returns false if the renderer was cancelled before
+    /// all passes were done, in which case only the passes finished by then are added.
+    pub fn render(&self, renderer: &ProgressiveRenderer, pass_samples: &[u32]) -> io::Result<bool> {
+        let tiles = renderer.tiles();
+        let first_pass = renderer.passes();
+        let jobs: VecDeque<Job> = pass_samples
+            .iter()
+            .enumerate()
+            .flat_map(|(p, samples)| tiles.iter().map(move |tile| (p as u32, *samples, *tile)))
+            .enumerate()
+            .map(|(id, (p, samples, tile))| Job {
+                id,
+                pass: first_pass + p,
+                samples,
+                tile,
+                bounds: renderer.tile_film_bounds(&tile),
+            })
+            .collect();
+        let count = jobs.len();
+        let queue = Mutex::new(Queue {
+            pending: jobs,
+            results: (0..count).map(|_| None).collect(),
+            remaining: count,
+            size: renderer.size(),
+        });
+        let changed = Condvar::new();
+
+        let finished = thread::scope(|scope| -> io::Result<bool> {
+            loop {
+                {
+                    let queue = queue.lock().unwrap();
+                    if queue.remaining == 0 {
+                        // wake connections waiting for work so they can tell their workers to stop
+                        changed.notify_all();
+                        return Ok(true);
+                    }
+                }
+                if renderer.is_cancelled() {
+                    let mut queue = queue.lock().unwrap();
+                    queue.pending.clear();
+                    queue.remaining = 0;
+                    changed.notify_all();
+                    return Ok(false);
+                }
+
+                match self.listener.accept() {
+                    Ok((stream, _)) => {
+                        stream.set_nonblocking(false)?;
+                        stream.set_read_timeout(Some(self.timeout))?;
+                        stream.set_write_timeout(Some(self.timeout))?;
+                        let (queue, changed, scene) = (&queue, &changed, &self.scene);
+                        scope.spawn(move || {
+                            // a worker that goes away simply stops taking tiles
+                            let _ = serve_worker(stream, scene, queue, changed);
+                        });
+                    }
+                    Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => {
+                        let queue = queue.lock().unwrap();
+                        let _ = changed
+                            .wait_timeout(queue, Duration::from_millis(20))
+                            .unwrap();
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+        })?;
+
+        let mut results = queue.into_inner().unwrap().results.into_iter();
+        for samples in pass_samples {
+            let films: Option<Vec<Film>> = results.by_ref().take(tiles.len()).collect();
+            match films {
+                Some(films) => renderer.add_pass(&films, *samples),
+                // a cancelled render stops at the first pass with tiles missing
+                None => break,
+            }
+        }
+        Ok(finished)
+    }
+}
+
+/// Hands jobs to one worker connection until there are none left.
+fn serve_worker(
+    stream: TcpStream,
+    scene: &[String],
+    queue: &Mutex<Queue>,
+    changed: &Condvar,
+) -> io::Result<()> {
+    let size = queue.lock().unwrap().size;
+    let mut input = BufReader::new(stream.try_clone()?);
+    let mut output = BufWriter::new(stream);
+    output.write_all(MAGIC)?;
+    write_u32(&mut output, VERSION)?;
+    write_u32(&mut output, scene.len() as u32)?;
+    for argument in scene {
+        write_u32(&mut output, argument.len() as u32)?;
+        output.write_all(argument.as_bytes())?;
+    }
+    output.flush()?;
+
+    loop {
+        let job = {
+            let mut queue = queue.lock().unwrap();
+            loop {
+                if let Some(job) = queue.pending.pop_front() {
+                    break Some(job);
+                }
+                if queue.remaining == 0 {
+                    break None;
+                }
+                // other workers still hold jobs that may come back if they disconnect
+                queue = changed.wait(queue).unwrap();
+            }
+        };
+
+        let job = match job {
+            Some(job) => job,
+            None => {
+                output.write_all(&[DONE])?;
+                return output.flush();
+            }
+        };
+
+        let answer = send_job(&mut output, &job)
+            .and_then(|_| Film::read_from(&mut input))
+            .and_then(|film| {
+                if (film.columns(), film.rows()) == size && film.bounds() == job.bounds {
+                    Ok(film)
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("Worker sent the wrong pixels for tile {:?}", job.tile),
+                    ))
+                }
+            });
+        // a timeout fails the read like a disconnect, so the job goes back in the queue
+        match answer {
+            Ok(film) => {
+                let mut queue = queue.lock().unwrap();
+                if queue.results[job.id].is_none() && queue.remaining > 0 {
+                    queue.results[job.id] = Some(film);
+                    queue.remaining -= 1;
+                }
+                changed.notify_all();
+            }
+            Err(err) => {
+                let mut queue = queue.lock().unwrap();
+                if queue.remaining > 0 {
+                    queue.pending.push_front(job);
+                }
+                changed.notify_all();
+                return Err(err);
+            }
+        }
+    }
+}
+
+fn send_job<W: Write>(output: &mut W, job: &Job) -> io::Result<()> {
+    output.write_all(&[JOB])?;
+    for value in &[
+        job.pass,
+        job.samples,
+        job.tile.x,
+        job.tile.y,
+        job.tile.width,
+        job.tile.height,
+    ] {
+        write_u32(output, *value)?;
+    }
+    output.flush()
+}
+
+/// A worker's connection to a coordinator.
+pub struct WorkerConnection {
+    input: BufReader<TcpStream>,
+    output: BufWriter<TcpStream>,
+    scene: Vec<String>,
+}
+
+impl WorkerConnection {
+    /// Connects to a coordinator and receives the scene description to render.
+    pub fn connect<A: ToSocketAddrs>(address: A) -> io::Result<WorkerConnection> {
+        let stream = TcpStream::connect(address)?;
+        let mut input = BufReader::new(stream.try_clone()?);
+        let output = BufWriter::new(stream);
+
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC || read_u32(&mut input)? != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Not a render coordinator",
+            ));
+        }
+        let too_long =
+            || io::Error::new(io::ErrorKind::InvalidData, "Scene description is too long");
+        let count = read_u32(&mut input)?;
+        if count > LONGEST_SCENE {
+            return Err(too_long());
+        }
+        let mut scene = vec![];
+        let mut left = LONGEST_SCENE;
+        for _ in 0..count {
+            let len = read_u32(&mut input)?;
+            left = left.checked_sub(len).ok_or_else(too_long)?;
+            let mut argument = vec![0; len as usize];
+            input.read_exact(&mut argument)?;
+            scene.push(
+                String::from_utf8(argument)
+                    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            );
+        }
+
+        Ok(WorkerConnection {
+            input,
+            output,
+            scene,
+        })
+    }
+
+    /// The description of the scene, from which the worker builds its renderer.
+    pub fn scene(&self) -> &[String] {
+        &self.scene
+    }
+
+    /// Renders the tiles the coordinator asks for until it has no more.
+    /// Returns the number of tiles rendered.
+    pub fn serve(&mut self, renderer: &ProgressiveRenderer) -> io::Result<u32> {
+        let mut rendered = 0;
+        loop {
+            let mut kind = [0];
+            self.input.read_exact(&mut kind)?;
+            match kind[0] {
+                DONE => return Ok(rendered),
+                JOB => {
+                    let pass = read_u32(&mut self.input)?;
+                    let samples = read_u32(&mut self.input)?;
+                    let tile = Tile {
+                        x: read_u32(&mut self.input)?,
+                        y: read_u32(&mut self.input)?,
+                        width: read_u32(&mut self.input)?,
+                        height: read_u32(&mut self.input)?,
+                    };
+                    if tile.intersection(&renderer.sampled_region()) != Some(tile) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("Tile {:?} lies outside the render", tile),
+                        ));
+                    }
+                    renderer
+                        .render_tile(&tile, pass, samples)
+                        .write_to(&mut self.output)?;
+                    self.output.flush()?;
+                    rendered += 1;
+                }
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Unknown message from coordinator",
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        BoxFilter, Color, Lambertian, PerspectiveCamera, Point3, Sphere, TileOrder, Vec3, World,
+    };
+    use super::*;
+
+    fn scene(description: &[String]) -> (World, PerspectiveCamera) {
+        assert_eq!(description, ["one sphere", "--seed", "11"]);
+        let mut world = World::new();
+        world.push(Box::new(Sphere {
+            center: Point3::new(0.0, 0.0, -1.0),
+            radius: 0.5,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        }));
+        let camera = PerspectiveCamera::new(
+            Vec3::fill(0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        (world, camera)
+    }
+
+    fn description() -> Vec<String> {
+        vec![
+            String::from("one sphere"),
+            String::from("--seed"),
+            String::from("11"),
+        ]
+    }
+
+    fn worker(address: std::net::SocketAddr) -> u32 {
+        let mut connection = WorkerConnection::connect(address).unwrap();
+        let (world, camera) = scene(connection.scene());
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 12, 16, &filter);
+        renderer.set_seed(11);
+        connection.serve(&renderer).unwrap()
+    }
+
+    #[test]
+    fn workers_on_localhost_match_local_render() {
+        let (world, camera) = scene(&description());
+        let filter = BoxFilter { radius: 0.5 };
+
+        let mut local = ProgressiveRenderer::new(&world, &camera, 12, 16, &filter);
+        local.set_seed(11);
+        local.set_tiles(4, TileOrder::Hilbert);
+        local.render_pass(2);
+        local.render_pass(1);
+
+        let mut distributed = ProgressiveRenderer::new(&world, &camera, 12, 16, &filter);
+        distributed.set_seed(11);
+        distributed.set_tiles(4, TileOrder::Hilbert);
+        let coordinator = Coordinator::bind("127.0.0.1:0", &description()).unwrap();
+        let address = coordinator.local_addr().unwrap();
+
+        let workers: Vec<_> = (0..3)
+            .map(|_| thread::spawn(move || worker(address)))
+            .collect();
+        assert!(coordinator.render(&distributed, &[2, 1]).unwrap());
+        let tiles: u32 = workers.into_iter().map(|w| w.join().unwrap()).sum();
+
+        assert_eq!(tiles, 2 * 4 * 3);
+        assert_eq!(distributed.passes(), 2);
+        assert_eq!(distributed.samples_per_pixel(), 3);
+        assert_eq!(distributed.linear_image(), local.linear_image());
+    }
+
+    #[test]
+    fn disconnected_workers_tiles_are_reassigned() {
+        let (world, camera) = scene(&description());
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 12, 16, &filter);
+        renderer.set_seed(11);
+        let coordinator = Coordinator::bind("127.0.0.1:0", &description()).unwrap();
+        let address = coordinator.local_addr().unwrap();
+
+        let quitter = thread::spawn(move || {
+            // take a job and hang up without answering
+            let mut connection = WorkerConnection::connect(address).unwrap();
+            let mut kind = [0];
+            connection.input.read_exact(&mut kind).unwrap();
+            assert_eq!(kind[0], JOB);
+        });
+        let finisher = thread::spawn(move || {
+            quitter.join().unwrap();
+            worker(address)
+        });
+
+        assert!(coordinator.render(&renderer, &[1]).unwrap());
+        assert_eq!(finisher.join().unwrap(), renderer.tiles().len() as u32);
+        assert_eq!(renderer.passes(), 1);
+    }
+
+    #[test]
+    fn stalled_workers_tiles_are_reassigned() {
+        let (world, camera) = scene(&description());
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 12, 16, &filter);
+        renderer.set_seed(11);
+        let mut coordinator = Coordinator::bind("127.0.0.1:0", &description()).unwrap();
+        coordinator.set_timeout(Duration::from_millis(100));
+        let address = coordinator.local_addr().unwrap();
+
+        let staller = thread::spawn(move || {
+            // take a job and never answer, staying connected until the coordinator gives up
+            let mut connection = WorkerConnection::connect(address).unwrap();
+            let mut kind = [0];
+            connection.input.read_exact(&mut kind).unwrap();
+            assert_eq!(kind[0], JOB);
+            let _ = connection.input.read_to_end(&mut vec![]);
+        });
+        let finisher = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            worker(address)
+        });
+
+        assert!(coordinator.render(&renderer, &[1]).unwrap());
+        staller.join().unwrap();
+        assert_eq!(finisher.join().unwrap(), renderer.tiles().len() as u32);
+        assert_eq!(renderer.passes(), 1);
+    }
+
+    #[test]
+    fn films_for_the_wrong_pixels_are_refused() {
+        let (world, camera) = scene(&description());
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 12, 16, &filter);
+        renderer.set_seed(11);
+        let coordinator = Coordinator::bind("127.0.0.1:0", &description()).unwrap();
+        let address = coordinator.local_addr().unwrap();
+
+        let liar = thread::spawn(move || {
+            let mut connection = WorkerConnection::connect(address).unwrap();
+            let mut job = [0; 1 + 6 * 4];
+            connection.input.read_exact(&mut job).unwrap();
+            Film::new(3, 3).write_to(&mut connection.output).unwrap();
+            connection.output.flush().unwrap();
+            // the coordinator hangs up rather than sending another job
+            assert_eq!(connection.input.read(&mut job).unwrap(), 0);
+        });
+        let finisher = thread::spawn(move || {
+            liar.join().unwrap();
+            worker(address)
+        });
+
+        assert!(coordinator.render(&renderer, &[1]).unwrap());
+        assert_eq!(finisher.join().unwrap(), renderer.tiles().len() as u32);
+        assert_eq!(renderer.passes(), 1);
+    }
+
+    #[test]
+    fn long_scene_descriptions_are_refused() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let coordinator = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(MAGIC).unwrap();
+            write_u32(&mut stream, VERSION).unwrap();
+            write_u32(&mut stream, 2).unwrap();
+            write_u32(&mut stream, 16).unwrap();
+            stream.write_all(b"one sphere --see").unwrap();
+            write_u32(&mut stream, LONGEST_SCENE).unwrap();
+        });
+        assert!(WorkerConnection::connect(address).is_err());
+        coordinator.join().unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+use super::PerspectiveCamera;
+
+/// The height of a 35mm full frame sensor in scene units, taking those to be metres.
+pub const FULL_FRAME_SENSOR_HEIGHT: f64 = 0.024;
+
+/// Camera settings in photographic units. Together they set how much light reaches the
+/// film, so scenes lit with physical radiances come out as bright as a real camera
+/// would record them, and the f-stop also sets the depth of field.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exposure {
+    /// Focal length divided by the aperture diameter, e.g. 2.8 or 16.
+    pub f_stop: f64,
+    /// How long the shutter is open in seconds, e.g. 1/125.
+    pub shutter_speed: f64,
+    /// Film or sensor sensitivity, e.g. 100.
+    pub iso: f64,
+}
+
+impl Exposure {
+    /// The exposure value at ISO 100. Settings with the same value give the same brightness.
+    pub fn ev100(&self) -> f64 {
+        f64::log2(self.f_stop * self.f_stop / self.shutter_speed * 100.0 / self.iso)
+    }
+
+    /// Scales linear radiance to film brightness. The brightest radiance that doesn't
+    /// saturate is 1.2 * 2^EV100, following the standard saturation based sensitivity.
+    pub fn multiplier(&self) -> f64 {
+        1.0 / (1.2 * f64::powf(2.0, self.ev100()))
+    }
+
+    /// The diameter of the aperture for a lens of the given focal length.
+    pub fn aperture(&self, focal_length: f64) -> f64 {
+        focal_length / self.f_stop
+    }
+}
+
+impl PerspectiveCamera {
+    /// The focal length a lens needs to give this camera's field of view on a sensor
+    /// of the given height.
+    pub fn focal_length(&self, sensor_height: f64) -> f64 {
+        sensor_height * self.focus_distance() / self.vertical.length()
+    }
+
+    /// Sets the lens radius from the f-stop and the shutter interval from the shutter
+    /// speed, starting at shutter_open. The brightness is applied to the image separately,
+    /// with ProgressiveRenderer::set_exposure.
+    pub fn with_exposure(self, exposure: &Exposure, sensor_height: f64) -> PerspectiveCamera {
+        let lens_radius = exposure.aperture(self.focal_length(sensor_height)) / 2.0;
+        PerspectiveCamera {
+            lens_radius,
+            shutter_close: self.shutter_open + exposure.shutter_speed,
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Point3, Vec3};
+    use super::*;
+
+    #[test]
+    fn equivalent_exposures() {
+        let sunny_16 = Exposure {
+            f_stop: 16.0,
+            shutter_speed: 1.0 / 100.0,
+            iso: 100.0,
+        };
+        assert!((sunny_16.ev100() - 14.64).abs() < 0.01);
+        // opening up two stops and doubling the shutter speed twice gives the same exposure
+        let wide_open = Exposure {
+            f_stop: 8.0,
+            shutter_speed: 1.0 / 400.0,
+            iso: 100.0,
+        };
+        assert!((wide_open.multiplier() - sunny_16.multiplier()).abs() < 1e-12);
+        // while doubling the ISO doubles the brightness
+        let faster_film = Exposure {
+            iso: 200.0,
+            ..sunny_16
+        };
+        let ratio = faster_film.multiplier() / sunny_16.multiplier();
+        assert!((ratio - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn f_stop_sets_the_lens() {
+        // a 90 degree field of view on a full frame sensor needs a 12mm lens
+        let camera = PerspectiveCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.5,
+            0.0,
+            10.0,
+        );
+        let focal_length = camera.focal_length(FULL_FRAME_SENSOR_HEIGHT);
+        assert!((focal_length - 0.012).abs() < 1e-12);
+        // shifting doesn't change the lens
+        let shifted = camera.clone().with_shift(0.3, -0.2);
+        assert!((shifted.focal_length(FULL_FRAME_SENSOR_HEIGHT) - 0.012).abs() < 1e-12);
+
+        let exposure = Exposure {
+            f_stop: 2.0,
+            shutter_speed: 0.5,
+            iso: 100.0,
+        };
+        let camera = camera.with_exposure(&exposure, FULL_FRAME_SENSOR_HEIGHT);
+        assert!((camera.lens_radius - 0.003).abs() < 1e-12);
+        assert_eq!((camera.shutter_open, camera.shutter_close), (0.0, 0.5));
+    }
+}
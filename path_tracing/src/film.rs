@@ -0,0 +1,295 @@
+use super::{Color, Filter, Tile};
+use std::io::{self, Read, Write};
+
+/// A Film accumulates filtered samples for a section of an image.
+/// Each sample is splatted onto every pixel whose center lies within the filter radius,
+/// and pixels are resolved to the weighted average of the samples they received.
+pub struct Film {
+    columns: u32,
+    rows: u32,
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+    color: Vec<Color>,
+    weight: Vec<f64>,
+}
+
+impl Film {
+    /// Creates an empty film covering a whole image.
+    pub fn new(columns: u32, rows: u32) -> Film {
+        Film::section(columns, rows, 0, 0, columns, rows)
+    }
+
+    /// Creates an empty film covering pixels [x0, x1) x [y0, y1) of an image.
+    pub(crate) fn section(columns: u32, rows: u32, x0: u32, y0: u32, x1: u32, y1: u32) -> Film {
+        let len = (x1 - x0) as usize * (y1 - y0) as usize;
+        Film {
+            columns,
+            rows,
+            x0,
+            y0,
+            x1,
+            y1,
+            color: vec![Color::black(); len],
+            weight: vec![0.0; len],
+        }
+    }
+
+    pub fn columns(&self) -> u32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    /// The pixels the film covers.
+    pub(crate) fn bounds(&self) -> Tile {
+        Tile {
+            x: self.x0,
+            y: self.y0,
+            width: self.x1 - self.x0,
+            height: self.y1 - self.y0,
+        }
+    }
+
+    /// Whether the film covers every pixel of its image.
+    pub fn is_whole(&self) -> bool {
+        (self.x0, self.y0, self.x1, self.y1) == (0, 0, self.columns, self.rows)
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y - self.y0) as usize * (self.x1 - self.x0) as usize + (x - self.x0) as usize
+    }
+
+    /// Adds a sample taken at raster position (x, y) to the surrounding pixels.
+    pub fn add_sample(&mut self, x: f64, y: f64, color: Color, filter: &dyn Filter) {
+        let radius = filter.radius();
+        // pixel centers sit at half-integer raster coordinates
+        let first_x = f64::max((x - 0.5 - radius).ceil(), self.x0 as f64) as u32;
+        let last_x = f64::min((x - 0.5 + radius).floor(), self.x1 as f64 - 1.0);
+        let first_y = f64::max((y - 0.5 - radius).ceil(), self.y0 as f64) as u32;
+        let last_y = f64::min((y - 0.5 + radius).floor(), self.y1 as f64 - 1.0);
+        if last_x < first_x as f64 || last_y < first_y as f64 {
+            return;
+        }
+
+        for py in first_y..=(last_y as u32) {
+            for px in first_x..=(last_x as u32) {
+                let w = filter.evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+                if w != 0.0 {
+                    let i = self.index(px, py);
+                    self.color[i] += color * w;
+                    self.weight[i] += w;
+                }
+            }
+        }
+    }
+
+    /// Adds the samples gathered by another film of the same image into this one.
+    pub fn merge(&mut self, other: &Film) {
+        let x0 = u32::max(self.x0, other.x0);
+        let x1 = u32::min(self.x1, other.x1);
+        let y0 = u32::max(self.y0, other.y0);
+        let y1 = u32::min(self.y1, other.y1);
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let (i, j) = (self.index(x, y), other.index(x, y));
+                self.color[i] += other.color[j];
+                self.weight[i] += other.weight[j];
+            }
+        }
+    }
+
+    /// Writes the accumulated samples in a little-endian binary layout.
+    /// Values are stored bit for bit, so a film read back resolves identically.
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for value in &[self.columns, self.rows, self.x0, self.y0, self.x1, self.y1] {
+            write_u32(out, *value)?;
+        }
+        for (color, weight) in self.color.iter().zip(self.weight.iter()) {
+            for c in &color.channels() {
+                write_f64(out, *c)?;
+            }
+            write_f64(out, *weight)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a film written by write_to.
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<Film> {
+        let mut bounds = [0; 6];
+        for value in bounds.iter_mut() {
+            *value = read_u32(input)?;
+        }
+        let [columns, rows, x0, y0, x1, y1] = bounds;
+        if x0 > x1 || y0 > y1 || x1 > columns || y1 > rows {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Film section lies outside its image",
+            ));
+        }
+
+        let len = ((x1 - x0) as usize)
+            .checked_mul((y1 - y0) as usize)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "Film section is too large")
+            })?;
+
+        // grow as pixels arrive rather than trusting the header, so a corrupt one
+        // runs out of input instead of allocating the whole claimed size up front
+        let (mut color, mut weight) = (vec![], vec![]);
+        for _ in 0..len {
+            let r = read_f64(input)?;
+            let g = read_f64(input)?;
+            let b = read_f64(input)?;
+            color.push(Color::new(r, g, b));
+            weight.push(read_f64(input)?);
+        }
+        Ok(Film {
+            columns,
+            rows,
+            x0,
+            y0,
+            x1,
+            y1,
+            color,
+            weight,
+        })
+    }
+
+    /// Returns the linear color of each pixel in the film, row by row.
+    /// Pixels that received no samples are black.
+    pub fn resolve(&self) -> Vec<Color> {
+        self.color
+            .iter()
+            .zip(self.weight.iter())
+            .map(|(color, &weight)| {
+                if weight > 0.0 {
+                    let c = *color / weight;
+                    // negative filter lobes can push dim pixels below zero
+                    c.map(|c| c.max(0.0))
+                } else {
+                    Color::black()
+                }
+            })
+            .collect()
+    }
+}
+
+pub(crate) fn write_u32<W: Write>(out: &mut W, value: u32) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_u64<W: Write>(out: &mut W, value: u64) -> io::Result<()> {
+    out.write_all(&value.to_le_bytes())
+}
+
+pub(crate) fn write_f64<W: Write>(out: &mut W, value: f64) -> io::Result<()> {
+    write_u64(out, value.to_bits())
+}
+
+pub(crate) fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub(crate) fn read_f64<R: Read>(input: &mut R) -> io::Result<f64> {
+    Ok(f64::from_bits(read_u64(input)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{BoxFilter, TentFilter};
+    use super::*;
+
+    #[test]
+    fn box_filter_stays_in_pixel() {
+        let mut film = Film::new(2, 2);
+        let filter = BoxFilter { radius: 0.5 };
+        film.add_sample(0.25, 0.75, Color::fill(1.0), &filter);
+        film.add_sample(1.5, 1.5, Color::fill(0.5), &filter);
+        film.add_sample(1.25, 1.25, Color::fill(1.5), &filter);
+
+        assert_eq!(
+            film.resolve(),
+            vec![
+                Color::fill(1.0),
+                Color::fill(0.0),
+                Color::fill(0.0),
+                Color::fill(1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn samples_on_a_pixel_edge_land_once() {
+        let mut film = Film::new(2, 1);
+        let filter = BoxFilter { radius: 0.5 };
+        film.add_sample(1.0, 0.5, Color::fill(1.0), &filter);
+        film.add_sample(0.5, 0.5, Color::fill(0.5), &filter);
+        assert_eq!(film.resolve(), vec![Color::fill(0.5), Color::fill(1.0)]);
+    }
+
+    #[test]
+    fn wide_filters_splat_to_neighbors() {
+        let mut film = Film::new(3, 1);
+        let filter = TentFilter { radius: 1.5 };
+        film.add_sample(1.5, 0.5, Color::fill(1.0), &filter);
+        let colors = film.resolve();
+        assert!(colors.iter().all(|c| *c == Color::fill(1.0)));
+    }
+
+    #[test]
+    fn merge_sections() {
+        let filter = TentFilter { radius: 1.5 };
+        let mut whole = Film::new(4, 4);
+        whole.add_sample(1.2, 1.7, Color::fill(1.0), &filter);
+        whole.add_sample(2.6, 2.1, Color::fill(0.25), &filter);
+
+        let mut top = Film::section(4, 4, 0, 0, 4, 3);
+        top.add_sample(1.2, 1.7, Color::fill(1.0), &filter);
+        let mut bottom = Film::section(4, 4, 0, 1, 4, 4);
+        bottom.add_sample(2.6, 2.1, Color::fill(0.25), &filter);
+
+        let mut merged = Film::new(4, 4);
+        merged.merge(&top);
+        merged.merge(&bottom);
+        assert_eq!(merged.resolve(), whole.resolve());
+    }
+
+    #[test]
+    fn serialize_round_trip() {
+        let filter = TentFilter { radius: 1.5 };
+        let mut film = Film::section(5, 4, 1, 1, 4, 3);
+        film.add_sample(2.2, 1.7, Color::new(0.1, 0.2, 0.3), &filter);
+
+        let mut bytes = vec![];
+        film.write_to(&mut bytes).unwrap();
+        let read = Film::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!((read.columns(), read.rows()), (5, 4));
+        assert_eq!(read.resolve(), film.resolve());
+
+        assert!(Film::read_from(&mut &bytes[..10]).is_err());
+        assert!(!read.is_whole() && Film::new(5, 4).is_whole());
+    }
+
+    #[test]
+    fn huge_headers_run_out_of_input() {
+        let mut bytes = vec![];
+        for value in &[u32::MAX, u32::MAX, 0, 0, u32::MAX, u32::MAX] {
+            write_u32(&mut bytes, *value).unwrap();
+        }
+        write_f64(&mut bytes, 1.0).unwrap();
+        let error = Film::read_from(&mut bytes.as_slice()).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
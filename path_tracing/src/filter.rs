@@ -0,0 +1,208 @@
+use std::f64::consts::PI;
+use std::fmt::Debug;
+
+/// Reconstruction filters weight how much a sample contributes to the pixels around it.
+/// Filters are evaluated at the offset between the sample and a pixel center, in pixels.
+/// The Debug form names the filter and its parameters, so checkpoints can tell filters apart.
+pub trait Filter: Debug {
+    /// Offsets beyond the radius (on either axis) have no weight.
+    fn radius(&self) -> f64;
+    fn evaluate(&self, x: f64, y: f64) -> f64;
+}
+
+/// Box filtering gives every sample within the radius equal weight.
+/// A radius of 0.5 averages the samples taken inside each pixel.
+#[derive(Debug)]
+pub struct BoxFilter {
+    pub radius: f64,
+}
+
+impl Filter for BoxFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        // half-open, so a sample on the edge between two pixels only lands in one of them
+        let inside = |offset: f64| -self.radius < offset && offset <= self.radius;
+        if inside(x) && inside(y) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Tent filtering falls off linearly from the pixel center.
+#[derive(Debug)]
+pub struct TentFilter {
+    pub radius: f64,
+}
+
+impl Filter for TentFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        f64::max(0.0, self.radius - x.abs()) * f64::max(0.0, self.radius - y.abs())
+    }
+}
+
+/// Gaussian filtering, offset so the weight reaches zero at the radius.
+/// Larger alpha values give a narrower, sharper falloff.
+#[derive(Debug)]
+pub struct GaussianFilter {
+    pub radius: f64,
+    pub alpha: f64,
+}
+
+impl GaussianFilter {
+    fn gaussian(&self, d: f64) -> f64 {
+        let edge = f64::exp(-self.alpha * self.radius * self.radius);
+        f64::max(0.0, f64::exp(-self.alpha * d * d) - edge)
+    }
+}
+
+impl Filter for GaussianFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+/// Mitchell-Netravali cubic filtering.
+/// The b and c parameters trade blurring against ringing; b = c = 1/3 is the usual choice.
+#[derive(Debug)]
+pub struct MitchellFilter {
+    pub radius: f64,
+    pub b: f64,
+    pub c: f64,
+}
+
+impl MitchellFilter {
+    fn mitchell(&self, d: f64) -> f64 {
+        let (b, c) = (self.b, self.c);
+        // the cubic is defined over [-2, 2], so scale the offset to fit the radius
+        let x = (2.0 * d / self.radius).abs();
+        if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b))
+                / 6.0
+        }
+    }
+}
+
+impl Filter for MitchellFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+/// Lanczos windowed sinc filtering.
+/// Tau is the number of sinc lobes that fit within the window.
+#[derive(Debug)]
+pub struct LanczosFilter {
+    pub radius: f64,
+    pub tau: f64,
+}
+
+impl LanczosFilter {
+    fn windowed_sinc(&self, d: f64) -> f64 {
+        let d = d.abs();
+        if d > self.radius {
+            0.0
+        } else {
+            sinc(d) * sinc(d / self.tau)
+        }
+    }
+}
+
+impl Filter for LanczosFilter {
+    fn radius(&self) -> f64 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.windowed_sinc(x) * self.windowed_sinc(y)
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        f64::sin(PI * x) / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_relative_eq;
+
+    #[test]
+    fn box_filter_is_flat() {
+        let filter = BoxFilter { radius: 0.5 };
+        assert_eq!(filter.evaluate(0.0, 0.0), 1.0);
+        assert_eq!(filter.evaluate(0.4, -0.4), 1.0);
+        assert_eq!(filter.evaluate(0.6, 0.0), 0.0);
+        assert_eq!(filter.evaluate(0.5, 0.0), 1.0);
+        assert_eq!(filter.evaluate(-0.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn filters_vanish_at_radius() {
+        let filters: Vec<Box<dyn Filter>> = vec![
+            Box::new(TentFilter { radius: 1.0 }),
+            Box::new(GaussianFilter {
+                radius: 1.5,
+                alpha: 2.0,
+            }),
+            Box::new(MitchellFilter {
+                radius: 2.0,
+                b: 1.0 / 3.0,
+                c: 1.0 / 3.0,
+            }),
+            Box::new(LanczosFilter {
+                radius: 3.0,
+                tau: 3.0,
+            }),
+        ];
+
+        for filter in filters {
+            let r = filter.radius();
+            assert!(filter.evaluate(0.0, 0.0) > 0.0);
+            assert_relative_eq!(filter.evaluate(r, 0.0), 0.0, epsilon = 1e-9);
+            assert_relative_eq!(filter.evaluate(0.0, -r), 0.0, epsilon = 1e-9);
+            assert_eq!(filter.evaluate(r + 0.1, 0.0), 0.0);
+        }
+    }
+
+    #[test]
+    fn filters_are_symmetric() {
+        let filter = MitchellFilter {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        };
+        assert_relative_eq!(filter.evaluate(0.3, 0.7), filter.evaluate(-0.3, -0.7));
+        assert_relative_eq!(filter.evaluate(0.3, 0.7), filter.evaluate(0.7, 0.3));
+    }
+}
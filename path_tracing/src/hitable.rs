@@ -1,27 +1,37 @@
-use super::Scattering;
-use super::{Ray, Vec3};
+use super::{Aabb, Scattering};
+use super::{Normal3, Point3, Ray, Vec3};
 
 ///
 /// HitRecords store information about a ray intersection with a Hitable surface or volume.
 ///
 pub struct HitRecord<'a> {
     pub t: f64,
-    pub p: Vec3,
-    pub normal: Vec3,
+    pub p: Point3,
+    pub normal: Normal3,
+    /// Surface coordinates of the hit, for texturing.
+    pub u: f64,
+    pub v: f64,
     pub material: &'a dyn Scattering,
 }
 
 impl<'a> HitRecord<'a> {
+    /// The normal as a direction, for scattering rays.
     pub fn normal(&self) -> &Vec3 {
-        &self.normal
+        &self.normal.0
     }
 
-    pub fn position(&self) -> &Vec3 {
+    pub fn position(&self) -> &Point3 {
         &self.p
     }
 }
 
 /// Hitable types can reflect rays for tracing
 pub trait Hitable {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>>;
+
+    /// A box containing the object for the whole of [time0, time1], so that a moving
+    /// object can't escape it while the shutter is open. None means it is unbounded.
+    fn bounding_box(&self, _time0: f64, _time1: f64) -> Option<Aabb> {
+        None
+    }
 }
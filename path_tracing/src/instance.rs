@@ -0,0 +1,248 @@
+use super::{Aabb, HitRecord, Hitable};
+use super::{AnimatedTransform, Ray, Transform, TransformAnimation};
+use std::sync::Arc;
+
+/// Places a Hitable in the scene with a transform.
+/// The object is shared, so it can be placed many times without copying it.
+pub struct Transformed<H: Hitable + ?Sized> {
+    pub object: Arc<H>,
+    pub transform: Transform,
+}
+
+impl<H: Hitable + ?Sized> Transformed<H> {
+    pub fn new(object: Arc<H>, transform: Transform) -> Transformed<H> {
+        Transformed { object, transform }
+    }
+}
+
+impl<H: Hitable + ?Sized> Hitable for Transformed<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_transformed(&*self.object, &self.transform, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let bounds = self.object.bounding_box(time0, time1)?;
+        Some(self.transform.bounds(&bounds))
+    }
+}
+
+/// Places a Hitable in the scene with a transform that moves while the shutter is open.
+pub struct Animated<H: Hitable + ?Sized> {
+    pub object: Arc<H>,
+    pub transform: AnimatedTransform,
+}
+
+impl<H: Hitable + ?Sized> Animated<H> {
+    pub fn new(object: Arc<H>, transform: AnimatedTransform) -> Animated<H> {
+        Animated { object, transform }
+    }
+}
+
+impl<H: Hitable + ?Sized> Hitable for Animated<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let transform = self.transform.at(ray.time());
+        hit_transformed(&*self.object, &transform, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let bounds = self.object.bounding_box(time0, time1)?;
+        Some(self.transform.bounds(&bounds))
+    }
+}
+
+/// Places a Hitable in the scene with keyframed motion, following the time of each ray.
+/// Rendering frames at different shutter times moves it without rebuilding the scene.
+/// Every hit test looks up the keys and builds the transform for the ray's time, which
+/// costs about as much as hitting a sphere. For a single still frame, placing the object
+/// with Transformed and TransformAnimation::at avoids that.
+pub struct Keyframed<H: Hitable + ?Sized> {
+    pub object: Arc<H>,
+    pub animation: TransformAnimation,
+}
+
+impl<H: Hitable + ?Sized> Keyframed<H> {
+    pub fn new(object: Arc<H>, animation: TransformAnimation) -> Keyframed<H> {
+        Keyframed { object, animation }
+    }
+}
+
+impl<H: Hitable + ?Sized> Hitable for Keyframed<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let transform = self.animation.at(ray.time())?;
+        hit_transformed(&*self.object, &transform, ray, t_min, t_max)
+    }
+
+    // no bounding box: Catmull-Rom curves can overshoot their keys, so the keys don't bound the motion
+}
+
+fn hit_transformed<'a, H: Hitable + ?Sized>(
+    object: &'a H,
+    transform: &Transform,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    // the direction is not normalized, so distances along the ray stay the same
+    let to_object = transform.inverse();
+    let local = Ray::at_time(
+        to_object.point(*ray.origin()),
+        to_object.vector(*ray.direction()),
+        ray.time(),
+    );
+    object.hit(&local, t_min, t_max).map(|hit| HitRecord {
+        p: ray.point_at_parameter(hit.t),
+        normal: transform.normal(hit.normal).normalize(),
+        ..hit
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::stats::unflushed_intersection_tests;
+    use super::super::{
+        normalize, Color, Cuboid, Lambertian, Normal3, Point3, Sphere, Vec3, World,
+    };
+    use super::*;
+
+    #[test]
+    fn instances_share_an_object() {
+        let sphere = Arc::new(Sphere {
+            center: Point3::origin(),
+            radius: 1.0,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        });
+        let left = Transformed::new(
+            sphere.clone(),
+            Transform::translation(Vec3::new(-3.0, 0.0, 0.0)),
+        );
+        let right = Transformed::new(
+            sphere.clone(),
+            Transform::translation(Vec3::new(3.0, 0.0, 0.0))
+                * Transform::scaling(Vec3::fill(2.0)).unwrap(),
+        );
+        assert_eq!(Arc::strong_count(&sphere), 3);
+
+        let ray = Ray::new(Vec3::new(-3.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = left.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert_eq!(hit.p, Point3::new(-3.0, 0.0, 1.0));
+        assert!(right.hit(&ray, 0.0, f64::MAX).is_none());
+
+        let ray = Ray::new(Vec3::new(3.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = right.hit(&ray, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.t, 3.0);
+        assert_eq!(hit.normal, Normal3::new(0.0, 0.0, 1.0));
+
+        let mut world = World::new();
+        world.push(Box::new(left));
+        world.push(Box::new(right));
+    }
+
+    #[test]
+    fn animated_instances_move_with_time() {
+        let sphere = Arc::new(Sphere {
+            center: Point3::origin(),
+            radius: 1.0,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        });
+        let start = Transform::identity();
+        let end = Transform::translation(Vec3::new(4.0, 0.0, 0.0));
+        let moving = Animated::new(
+            sphere,
+            AnimatedTransform::new(start, end, 0.0, 1.0).unwrap(),
+        );
+        let at = |time| Ray::at_time(Vec3::new(4.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), time);
+        assert!(moving.hit(&at(0.0), 0.0, f64::MAX).is_none());
+        let hit = moving.hit(&at(1.0), 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.p, Point3::new(4.0, 0.0, 1.0));
+
+        let bounds = moving.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!(bounds.min, Point3::new(-1.0, -1.0, -1.0));
+        assert_eq!(bounds.max, Point3::new(5.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn keyframed_instances_follow_ray_time() {
+        let sphere = Arc::new(Sphere {
+            center: Point3::origin(),
+            radius: 1.0,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        });
+        let mut animation = TransformAnimation::default();
+        animation.translation.insert(1.0, Vec3::fill(0.0));
+        animation.translation.insert(2.0, Vec3::new(4.0, 0.0, 0.0));
+        let moving = Keyframed::new(sphere, animation);
+        let at = |time| Ray::at_time(Vec3::new(4.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0), time);
+        assert!(moving.hit(&at(0.0), 0.0, f64::MAX).is_none());
+        let hit = moving.hit(&at(2.5), 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.p, Point3::new(4.0, 0.0, 1.0));
+        assert!(moving.bounding_box(0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn instances_count_the_tests_of_what_they_hold() {
+        let material = || {
+            Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            })
+        };
+        let cube = Arc::new(Cuboid {
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 1.0, 1.0),
+            material: material(),
+        });
+        let mut world = World::new();
+        world.push(Box::new(Transformed::new(
+            cube,
+            Transform::translation(Vec3::new(3.0, 0.0, 0.0)),
+        )));
+        world.push(Box::new(Sphere {
+            center: Point3::origin(),
+            radius: 1.0,
+            material: material(),
+        }));
+        // a scale that reaches zero leaves nothing to test
+        let mut animation = TransformAnimation::default();
+        animation.scale.insert(0.0, Vec3::fill(0.0));
+        animation.scale.insert(1.0, Vec3::fill(1.0));
+        world.push(Box::new(Keyframed::new(
+            Arc::new(Sphere {
+                center: Point3::origin(),
+                radius: 1.0,
+                material: material(),
+            }),
+            animation,
+        )));
+
+        let before = unflushed_intersection_tests();
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(world.hit(&ray, 0.0, f64::MAX).is_some());
+        // six faces of the cube and the sphere
+        assert_eq!(unflushed_intersection_tests() - before, 7);
+    }
+
+    #[test]
+    fn normals_follow_uneven_scaling() {
+        let ellipsoid = Transformed::new(
+            Arc::new(Sphere {
+                center: Point3::origin(),
+                radius: 1.0,
+                material: Box::new(Lambertian {
+                    albedo: Color::fill(0.5),
+                }),
+            }),
+            Transform::scaling(Vec3::new(1.0, 4.0, 1.0)).unwrap(),
+        );
+        // hit the ellipsoid halfway up its side, where the surface slopes steeply
+        let ray = Ray::new(Vec3::new(5.0, 2.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let hit = ellipsoid.hit(&ray, 0.0, f64::MAX).unwrap();
+        let expected = normalize(Vec3::new(hit.p.x(), hit.p.y() / 16.0, 0.0));
+        assert!((hit.normal.0 - expected).length() < 1e-12);
+    }
+}
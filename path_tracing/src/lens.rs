@@ -0,0 +1,367 @@
+use super::camera::{look_basis, shutter_time};
+use super::{dot, random_in_unit_disk, refract, Camera, Point3, Ray, Vec3};
+
+/// A double-Gauss 50mm f/2 lens, from US patent 2,673,491 scaled to 50mm.
+pub const DOUBLE_GAUSS_50MM: &str = "# radius thickness ior aperture
+29.475   3.76   1.67   25.2
+84.83    0.12   1      25.2
+19.275   4.025  1.67   23
+40.77    3.275  1.699  23
+12.75    5.705  1      18
+0        4.5    0      17.1
+-14.495  1.18   1.603  17
+40.77    6.065  1.658  17
+-20.385  0.19   1      17
+437.065  3.22   1.717  20
+-39.73   0      1      20
+";
+
+/// One surface of a lens prescription. Lengths are in millimetres.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensElement {
+    /// Radius of curvature, positive when the center is towards the film.
+    /// Zero for the aperture stop, which is a flat opening rather than a surface.
+    pub radius: f64,
+    /// Distance along the axis to the next surface. The last surface's is replaced
+    /// by the distance to the film when the lens is focused.
+    pub thickness: f64,
+    /// Refractive index of what follows the surface, with 0 or 1 for air.
+    pub ior: f64,
+    /// Diameter of the surface's clear opening.
+    pub aperture: f64,
+}
+
+/// A lens made of spherical surfaces, listed from the front of the lens to the back.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lens {
+    pub elements: Vec<LensElement>,
+}
+
+impl Lens {
+    /// Reads a prescription with one surface per line, giving its radius, thickness,
+    /// refractive index and aperture diameter separated by spaces.
+    /// Blank lines and lines starting with # are skipped.
+    pub fn parse(text: &str) -> Result<Lens, String> {
+        let mut elements = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values: Vec<f64> = line
+                .split_whitespace()
+                .map(|value| value.parse::<f64>())
+                .collect::<Result<_, _>>()
+                .map_err(|_| format!("Line {}: expected numbers, got {}", number + 1, line))?;
+            match values.as_slice() {
+                &[radius, thickness, ior, aperture] if aperture > 0.0 && thickness >= 0.0 => {
+                    elements.push(LensElement {
+                        radius,
+                        thickness,
+                        ior: if ior == 0.0 { 1.0 } else { ior },
+                        aperture,
+                    })
+                }
+                _ => {
+                    return Err(format!(
+                        "Line {}: expected radius, thickness, ior and aperture, got {}",
+                        number + 1,
+                        line
+                    ))
+                }
+            }
+        }
+        if elements.is_empty() {
+            return Err(String::from("The lens has no surfaces"));
+        }
+        Ok(Lens { elements })
+    }
+
+    pub fn double_gauss() -> Lens {
+        Lens::parse(DOUBLE_GAUSS_50MM).expect("the built in lens parses")
+    }
+
+    /// Where each surface is on the axis, with the film at z = 0 and the scene towards -z.
+    fn positions(&self, film_distance: f64) -> Vec<f64> {
+        let mut z = -film_distance;
+        let mut positions = vec![0.0; self.elements.len()];
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            if i + 1 < self.elements.len() {
+                z -= element.thickness;
+            }
+            positions[i] = z;
+        }
+        positions
+    }
+
+    /// The refractive index in front of surface i.
+    fn ior_before(&self, i: usize) -> f64 {
+        if i == 0 {
+            1.0
+        } else {
+            self.elements[i - 1].ior
+        }
+    }
+
+    /// Follows a ray from the film out through the lens. Returns where it leaves the front
+    /// surface and its direction, or None if something inside the lens blocks it.
+    pub fn trace_from_film(
+        &self,
+        film_distance: f64,
+        origin: Vec3,
+        direction: Vec3,
+    ) -> Option<(Vec3, Vec3)> {
+        let positions = self.positions(film_distance);
+        let mut ray = (origin, direction);
+        for (i, element) in self.elements.iter().enumerate().rev() {
+            let eta = element.ior / self.ior_before(i);
+            ray = cross_surface(element, positions[i], ray, eta)?;
+        }
+        Some(ray)
+    }
+
+    /// Follows a ray from the scene in through the lens towards the film.
+    pub fn trace_from_scene(
+        &self,
+        film_distance: f64,
+        origin: Vec3,
+        direction: Vec3,
+    ) -> Option<(Vec3, Vec3)> {
+        let positions = self.positions(film_distance);
+        let mut ray = (origin, direction);
+        for (i, element) in self.elements.iter().enumerate() {
+            let eta = self.ior_before(i) / element.ior;
+            ray = cross_surface(element, positions[i], ray, eta)?;
+        }
+        Some(ray)
+    }
+
+    /// How far behind the last surface the film must be for things at distance in
+    /// front of the first surface to be sharp. None if the lens doesn't form an image there.
+    pub fn focus(&self, distance: f64) -> Option<f64> {
+        // follow a ray close to the axis and see where it crosses it behind the lens
+        let front = self.positions(0.0)[0];
+        let height = self.elements[0].aperture * 0.005;
+        let origin = Vec3::new(0.0, 0.0, front - distance);
+        let (p, d) = self.trace_from_scene(0.0, origin, Vec3::new(height, 0.0, distance))?;
+        if d.x() >= 0.0 {
+            return None;
+        }
+        let z = p.z() - p.x() * d.z() / d.x();
+        if z > 0.0 {
+            Some(z)
+        } else {
+            None
+        }
+    }
+}
+
+/// Takes a ray (origin, direction) across one surface at z, refracting it with the ratio of
+/// refractive indices eta. Returns None if it misses the opening or is totally reflected.
+fn cross_surface(
+    element: &LensElement,
+    z: f64,
+    (origin, direction): (Vec3, Vec3),
+    eta: f64,
+) -> Option<(Vec3, Vec3)> {
+    let (t, normal) = if element.radius == 0.0 {
+        ((z - origin.z()) / direction.z(), None)
+    } else {
+        let center = Vec3::new(0.0, 0.0, z + element.radius);
+        let oc = origin - center;
+        let a = dot(direction, direction);
+        let b = dot(oc, direction);
+        let c = dot(oc, oc) - element.radius * element.radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 {
+            return None;
+        }
+        // of the two crossings of the sphere, the surface is the one nearest z
+        let nearer = (direction.z() > 0.0) ^ (element.radius < 0.0);
+        let root = if nearer {
+            -discriminant.sqrt()
+        } else {
+            discriminant.sqrt()
+        };
+        let t = (-b + root) / a;
+        (t, Some((origin + direction * t - center) / element.radius))
+    };
+    if t <= 0.0 {
+        return None;
+    }
+    let p = origin + direction * t;
+    let half = element.aperture / 2.0;
+    if p.x() * p.x() + p.y() * p.y() > half * half {
+        return None;
+    }
+    match normal {
+        None => Some((p, direction)),
+        Some(normal) => {
+            let normal = if dot(direction, normal) > 0.0 {
+                -normal
+            } else {
+                normal
+            };
+            Some((p, refract(direction, normal, eta)?))
+        }
+    }
+}
+
+/// A camera that traces rays through a real lens design, so its images have the
+/// vignetting, distortion and defocus of that lens. Rays the lens blocks come out black,
+/// which darkens the corners, and also makes the image noisier than a thin lens camera.
+#[derive(Debug, Clone)]
+pub struct RealisticCamera {
+    pub origin: Point3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+    pub lens: Lens,
+    /// Distance from the last surface to the film, in millimetres.
+    pub film_distance: f64,
+    /// Size of the film in millimetres.
+    pub film_width: f64,
+    pub film_height: f64,
+    /// Scene units per millimetre, 0.001 for scenes in metres.
+    pub scale: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl RealisticCamera {
+    /// Points the lens from origin at target, focused at focus_dist in scene units
+    /// (taken to be metres) in front of the lens.
+    /// Returns None if the lens can't focus at that distance.
+    pub fn new<P: Into<Point3>>(
+        origin: P,
+        target: P,
+        up: Vec3,
+        lens: Lens,
+        (film_width, film_height): (f64, f64),
+        focus_dist: f64,
+    ) -> Option<RealisticCamera> {
+        let (origin, target) = (origin.into(), target.into());
+        let (u, v, w) = look_basis(origin, target, up);
+        let scale = 0.001;
+        let film_distance = lens.focus(focus_dist / scale)?;
+        Some(RealisticCamera {
+            origin,
+            u,
+            v,
+            w,
+            lens,
+            film_distance,
+            film_width,
+            film_height,
+            scale,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        })
+    }
+
+    pub fn with_shutter(self, open: f64, close: f64) -> RealisticCamera {
+        RealisticCamera {
+            shutter_open: open,
+            shutter_close: close,
+            ..self
+        }
+    }
+
+    fn to_scene(&self, v: Vec3) -> Vec3 {
+        self.u * v.x() + self.v * v.y() + self.w * v.z()
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        // the lens turns the image upside down, so the film is read the other way round
+        let film = Vec3::new(
+            (0.5 - u) * self.film_width,
+            (0.5 - v) * self.film_height,
+            0.0,
+        );
+        let rear = self.lens.elements[self.lens.elements.len() - 1];
+        let [x, y] = random_in_unit_disk();
+        let pupil = Vec3::new(
+            x * rear.aperture / 2.0,
+            y * rear.aperture / 2.0,
+            -self.film_distance,
+        );
+        let (p, d) = self
+            .lens
+            .trace_from_film(self.film_distance, film, pupil - film)?;
+        Some(Ray::at_time(
+            self.origin + self.to_scene(p) * self.scale,
+            self.to_scene(d),
+            shutter_time(self.shutter_open, self.shutter_close),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{normalize, seed_random};
+    use super::*;
+
+    #[test]
+    fn parse_prescriptions() {
+        let lens = Lens::double_gauss();
+        assert_eq!(lens.elements.len(), 11);
+        // the aperture stop's ior of 0 means air
+        assert_eq!(lens.elements[5].ior, 1.0);
+        assert!(Lens::parse("# nothing here\n").is_err());
+        let error = Lens::parse("10 1 1.5 5\n10 1 1.5\n").unwrap_err();
+        assert!(error.starts_with("Line 2"), "{}", error);
+        assert!(Lens::parse("10 1 glass 5").is_err());
+    }
+
+    #[test]
+    fn focused_rays_meet() {
+        let lens = Lens::double_gauss();
+        let far = lens.focus(1e6).unwrap();
+        let near = lens.focus(500.0).unwrap();
+        // closer subjects need the lens further from the film
+        assert!(near > far && far > 20.0 && far < 60.0, "{} {}", near, far);
+
+        // rays from the center of the film all pass through the point in focus
+        let front = lens.positions(near)[0];
+        let subject = Vec3::new(0.0, 0.0, front - 500.0);
+        let film = Vec3::fill(0.0);
+        let mut traced = 0;
+        for &(x, y) in &[(1.0, 0.0), (0.0, 2.0), (-3.0, 1.0), (2.0, -2.0)] {
+            let pupil = Vec3::new(x, y, -near);
+            if let Some((p, d)) = lens.trace_from_film(near, film, pupil - film) {
+                let d = normalize(d);
+                let to_subject = subject - p;
+                let miss = to_subject - d * dot(to_subject, d);
+                assert!(miss.length() < 0.5, "missed by {}", miss.length());
+                traced += 1;
+            }
+        }
+        assert!(traced >= 3);
+    }
+
+    #[test]
+    fn corners_are_vignetted() {
+        seed_random(3);
+        let camera = RealisticCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Lens::double_gauss(),
+            (36.0, 24.0),
+            10.0,
+        )
+        .unwrap();
+        let through = |u, v| (0..400).filter(|_| camera.make_ray(u, v).is_some()).count();
+        let center = through(0.5, 0.5);
+        assert!(center > 100);
+        assert!(through(1.0, 1.0) < center);
+
+        let ray = (0..100).find_map(|_| camera.make_ray(0.5, 0.5)).unwrap();
+        assert!(ray.direction().z() < 0.0);
+        // the image is the right way up: the right of the image looks right
+        let right = (0..100).find_map(|_| camera.make_ray(0.9, 0.5)).unwrap();
+        assert!(right.direction().x() > 0.0);
+    }
+}
@@ -6,78 +6,92 @@
 //! Vector math routines and convenience functions for graphics.
 //!
 
+// the original tests exercise the operators and helpers on borrowed values on purpose
+#![cfg_attr(
+    test,
+    allow(
+        clippy::legacy_numeric_constants,
+        clippy::needless_borrows_for_generic_args,
+        clippy::op_ref,
+        clippy::unnecessary_cast
+    )
+)]
+
+mod aabb;
+mod animation;
+mod aperture;
 mod camera;
+mod cancel;
+mod checkpoint;
+mod color;
+mod distributed;
+mod exposure;
+mod film;
+mod filter;
 mod hitable;
+mod instance;
+mod lens;
+mod light;
+mod matrix;
+mod point;
+mod quaternion;
 mod ray;
+mod rect;
+mod renderer;
 mod scattering;
 mod sphere;
+mod stats;
+mod stereo;
+mod tiles;
+mod transform;
 mod utilities;
 mod vector;
 mod world;
 
+pub use self::aabb::*;
+pub use self::animation::*;
+pub use self::aperture::*;
 pub use self::camera::*;
+pub use self::cancel::*;
+pub use self::checkpoint::*;
+pub use self::color::*;
+pub use self::distributed::*;
+pub use self::exposure::*;
+pub use self::film::*;
+pub use self::filter::*;
 pub use self::hitable::*;
+pub use self::instance::*;
+pub use self::lens::*;
+pub use self::light::*;
+pub use self::matrix::*;
+pub use self::point::*;
+pub use self::quaternion::*;
 pub use self::ray::*;
+pub use self::rect::*;
+pub use self::renderer::*;
 pub use self::scattering::*;
 pub use self::sphere::*;
+pub use self::stats::*;
+pub use self::stereo::*;
+pub use self::tiles::*;
+pub use self::transform::*;
 pub use self::utilities::*;
 pub use self::vector::*;
 pub use self::world::*;
-use rand::prelude::*;
-use rayon::prelude::*;
-
-fn color(world: &World, ray: Ray, depth: u8) -> Vec3 {
-    if let Some(hit) = world.hit(&ray, 0.001, std::f64::MAX) {
-        // return (hit.normal + 1.0) * 0.5;
-        // recurse until you bounce off into the sky
-        if depth < 50 {
-            if let Some(reflection) = hit.material.scatter(&ray, &hit) {
-                // return (*reflection.ray.direction() + 1.0) * 0.5;
-                return color(world, reflection.ray, depth + 1) * reflection.attenuation;
-            } else {
-                return Vec3::new(0.0, 0.0, 0.0);
-            }
-        }
-    }
-
-    let unit_direction = normalize(ray.direction());
-    let t = 0.5 * (unit_direction.y() + 1.0);
-    mix(Vec3::new(1.0, 1.0, 1.0), Vec3::new(0.5, 0.7, 1.0), t)
-}
 
+/// Renders the world as seen by the camera, taking num_samples per pixel.
+/// Samples are splatted to nearby pixels with the reconstruction filter.
+/// Returns gamma-adjusted colors row by row.
+/// Use a ProgressiveRenderer directly for renders that can be cancelled or resumed.
 pub fn trace_scene(
     world: &World,
-    camera: &Camera,
+    camera: &dyn Camera,
     rows: u32,
     columns: u32,
     num_samples: u32,
-) -> Vec<Vec3> {
-    let colors: Vec<Vec3> = (0..(rows * columns))
-        .into_par_iter()
-        .map(|index| {
-            let x = index % columns;
-            let y = index / columns;
-            (x as i32, y as i32)
-        })
-        .map(|(x, y)| {
-            let mut rng = rand::thread_rng();
-            (0..num_samples)
-                .map(|_| (x as f64 + rng.gen::<f64>(), y as f64 + rng.gen::<f64>()))
-                .map(|(x, y)| (x / columns as f64, 1.0 - y / rows as f64))
-                .collect::<Vec<(f64, f64)>>()
-        }) // generate N normalized samples per pixel coordinate
-        .map(|samples| {
-            samples
-                .iter()
-                .map(|(u, v)| {
-                    let ray = camera.make_ray(*u, *v);
-                    return color(world, ray, 0);
-                })
-                .fold(Vec3::fill(0.0), |acc, x| acc + x)
-                / num_samples as f64
-        }) // accumulate colors for pixels
-        .map(|linear| Vec3::new(linear.r().sqrt(), linear.g().sqrt(), linear.b().sqrt())) // gamma adjust
-        .collect();
-
-    colors
+    filter: &(dyn Filter + Sync),
+) -> Vec<Color> {
+    let renderer = ProgressiveRenderer::new(world, camera, rows, columns, filter);
+    renderer.render_pass(num_samples);
+    renderer.image()
 }
@@ -0,0 +1,193 @@
+use super::{cross, dot, normalize, rand, Color, Point3, Vec3};
+use std::f64::consts::PI;
+
+/// Light arriving at a point from a Light, if nothing is in the way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightSample {
+    /// Unit vector from the point towards the light.
+    pub direction: Vec3,
+    /// How far the light is, so shadow rays stop there. Infinite for directional lights.
+    pub distance: f64,
+    /// Light per unit area on a surface facing the light.
+    pub irradiance: Color,
+}
+
+/// Lights with no size the integrator aims shadow rays at, since rays bouncing
+/// around the scene could never hit them by chance.
+pub trait Light: Send + Sync {
+    /// The light reaching p, or None if p is outside its reach.
+    fn sample(&self, p: Point3) -> Option<LightSample>;
+}
+
+/// A light shining equally in every direction from a point, fading with the square of the distance.
+pub struct PointLight {
+    pub position: Point3,
+    pub intensity: Color,
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        Some(LightSample {
+            direction: to_light / distance,
+            distance,
+            irradiance: self.intensity / (distance * distance),
+        })
+    }
+}
+
+/// A point light shining in a cone. It is at full strength within falloff_angle of the
+/// direction and fades smoothly to nothing at cone_angle, both in degrees.
+pub struct SpotLight {
+    pub position: Point3,
+    pub direction: Vec3,
+    pub intensity: Color,
+    pub cone_angle: f64,
+    pub falloff_angle: f64,
+}
+
+impl SpotLight {
+    /// How much of the light goes out at the given angle from the axis, in degrees.
+    /// Angles that aren't numbers, e.g. from a light with no direction, get none.
+    pub fn falloff(&self, angle: f64) -> f64 {
+        if angle.is_nan() || angle >= self.cone_angle {
+            return 0.0;
+        }
+        if angle <= self.falloff_angle {
+            return 1.0;
+        }
+        // smoothstep on the cosines, so the edge of the pool of light has no hard line
+        let (cos_cone, cos_falloff) = (
+            self.cone_angle.to_radians().cos(),
+            self.falloff_angle.to_radians().cos(),
+        );
+        let t = (angle.to_radians().cos() - cos_cone) / (cos_falloff - cos_cone);
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: Point3) -> Option<LightSample> {
+        let to_light = self.position - p;
+        let distance = to_light.length();
+        let direction = to_light / distance;
+        let cosine = dot(-direction, normalize(self.direction)).clamp(-1.0, 1.0);
+        let falloff = self.falloff(cosine.acos().to_degrees());
+        if falloff <= 0.0 {
+            return None;
+        }
+        Some(LightSample {
+            direction,
+            distance,
+            irradiance: self.intensity * (falloff / (distance * distance)),
+        })
+    }
+}
+
+/// A light far enough away that it shines the same way everywhere, like the sun.
+/// Directions are spread over a disc angular_radius degrees across as seen from the
+/// scene, so its shadows soften with distance from whatever casts them.
+pub struct DirectionalLight {
+    /// Direction from the scene towards the light.
+    pub direction: Vec3,
+    pub irradiance: Color,
+    pub angular_radius: f64,
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _: Point3) -> Option<LightSample> {
+        let axis = normalize(self.direction);
+        let direction = if self.angular_radius > 0.0 {
+            // uniform over the cone of directions that see the disc
+            let cos_max = self.angular_radius.to_radians().cos();
+            let cos_theta = 1.0 - rand() * (1.0 - cos_max);
+            let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+            let phi = 2.0 * PI * rand();
+            let helper = if axis.x().abs() > 0.9 {
+                Vec3::new(0.0, 1.0, 0.0)
+            } else {
+                Vec3::new(1.0, 0.0, 0.0)
+            };
+            let u = normalize(cross(helper, axis));
+            let v = cross(axis, u);
+            u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + axis * cos_theta
+        } else {
+            axis
+        };
+        Some(LightSample {
+            direction,
+            distance: f64::INFINITY,
+            irradiance: self.irradiance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_lights_fall_off_with_distance_squared() {
+        let light = PointLight {
+            position: Point3::new(0.0, 4.0, 0.0),
+            intensity: Color::fill(32.0),
+        };
+        let sample = light.sample(Point3::new(0.0, 2.0, 0.0)).unwrap();
+        assert_eq!(sample.direction, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(sample.distance, 2.0);
+        assert_eq!(sample.irradiance, Color::fill(8.0));
+        let further = light.sample(Point3::origin()).unwrap();
+        assert_eq!(further.irradiance, Color::fill(2.0));
+    }
+
+    #[test]
+    fn spot_lights_fade_towards_the_cone_edge() {
+        let light = SpotLight {
+            position: Point3::new(0.0, 1.0, 0.0),
+            direction: Vec3::new(0.0, -1.0, 0.0),
+            intensity: Color::fill(1.0),
+            cone_angle: 30.0,
+            falloff_angle: 20.0,
+        };
+        assert_eq!(light.falloff(10.0), 1.0);
+        assert_eq!(light.falloff(30.0), 0.0);
+        assert_eq!(light.falloff(f64::NAN), 0.0);
+        let halfway = light.falloff(25.0);
+        assert!(halfway > 0.3 && halfway < 0.7);
+        assert!(light.falloff(22.0) > light.falloff(28.0));
+
+        let below = light.sample(Point3::origin()).unwrap();
+        assert_eq!(below.irradiance, Color::fill(1.0));
+        // 45 degrees off the axis is outside the cone
+        assert!(light.sample(Point3::new(1.0, 0.0, 0.0)).is_none());
+        // and nothing shines backwards
+        assert!(light.sample(Point3::new(0.0, 2.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn directional_lights_spread_over_their_disc() {
+        let sharp = DirectionalLight {
+            direction: Vec3::new(0.0, 2.0, 0.0),
+            irradiance: Color::fill(3.0),
+            angular_radius: 0.0,
+        };
+        let sample = sharp.sample(Point3::origin()).unwrap();
+        assert_eq!(sample.direction, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(sample.distance, f64::INFINITY);
+
+        let soft = DirectionalLight {
+            angular_radius: 5.0,
+            ..sharp
+        };
+        let cos_max = 5f64.to_radians().cos();
+        let samples: Vec<Vec3> = (0..100)
+            .map(|_| soft.sample(Point3::origin()).unwrap().direction)
+            .collect();
+        for direction in &samples {
+            assert!((direction.length() - 1.0).abs() < 1e-12);
+            assert!(direction.y() >= cos_max - 1e-12);
+        }
+        assert!(samples.iter().any(|d| d.y() < 1.0 - 1e-6));
+    }
+}
@@ -0,0 +1,432 @@
+use super::{Point3, Vec3};
+use std::borrow::Borrow;
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// A 3x3 matrix stored row by row, for linear maps such as rotations and scaling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3(pub [[f64; 3]; 3]);
+
+/// A 4x4 matrix stored row by row, for affine transforms of points in homogeneous coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4(pub [[f64; 4]; 4]);
+
+impl Mat3 {
+    pub fn identity() -> Mat3 {
+        Mat3::diagonal(Vec3::fill(1.0))
+    }
+
+    pub fn diagonal(d: Vec3) -> Mat3 {
+        let &[x, y, z] = &d.0;
+        Mat3([[x, 0.0, 0.0], [0.0, y, 0.0], [0.0, 0.0, z]])
+    }
+
+    pub fn from_columns(x: Vec3, y: Vec3, z: Vec3) -> Mat3 {
+        Mat3([x.0, y.0, z.0]).transpose()
+    }
+
+    pub fn row(&self, i: usize) -> Vec3 {
+        Vec3(self.0[i])
+    }
+
+    pub fn column(&self, j: usize) -> Vec3 {
+        Vec3::new(self.0[0][j], self.0[1][j], self.0[2][j])
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        let m = &self.0;
+        Mat3([
+            [m[0][0], m[1][0], m[2][0]],
+            [m[0][1], m[1][1], m[2][1]],
+            [m[0][2], m[1][2], m[2][2]],
+        ])
+    }
+
+    pub fn determinant(&self) -> f64 {
+        let m = &self.0;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Returns the inverse, or None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat3> {
+        let determinant = self.determinant();
+        if determinant == 0.0 {
+            return None;
+        }
+        let m = &self.0;
+        // the adjugate, transposed cofactors
+        let adjugate = Mat3([
+            [
+                m[1][1] * m[2][2] - m[1][2] * m[2][1],
+                m[0][2] * m[2][1] - m[0][1] * m[2][2],
+                m[0][1] * m[1][2] - m[0][2] * m[1][1],
+            ],
+            [
+                m[1][2] * m[2][0] - m[1][0] * m[2][2],
+                m[0][0] * m[2][2] - m[0][2] * m[2][0],
+                m[0][2] * m[1][0] - m[0][0] * m[1][2],
+            ],
+            [
+                m[1][0] * m[2][1] - m[1][1] * m[2][0],
+                m[0][1] * m[2][0] - m[0][0] * m[2][1],
+                m[0][0] * m[1][1] - m[0][1] * m[1][0],
+            ],
+        ]);
+        Some(adjugate * (1.0 / determinant))
+    }
+}
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        Mat4::from_linear(&Mat3::identity(), Vec3::fill(0.0))
+    }
+
+    /// An affine matrix applying a linear map and then a translation.
+    pub fn from_linear(linear: &Mat3, translation: Vec3) -> Mat4 {
+        let (l, t) = (&linear.0, &translation.0);
+        Mat4([
+            [l[0][0], l[0][1], l[0][2], t[0]],
+            [l[1][0], l[1][1], l[1][2], t[1]],
+            [l[2][0], l[2][1], l[2][2], t[2]],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    /// The upper left 3x3 block, which holds everything but the translation of an affine matrix.
+    pub fn linear(&self) -> Mat3 {
+        let m = &self.0;
+        Mat3([
+            [m[0][0], m[0][1], m[0][2]],
+            [m[1][0], m[1][1], m[1][2]],
+            [m[2][0], m[2][1], m[2][2]],
+        ])
+    }
+
+    pub fn translation(&self) -> Vec3 {
+        Vec3::new(self.0[0][3], self.0[1][3], self.0[2][3])
+    }
+
+    /// True if the bottom row is (0, 0, 0, 1), so there is no projection.
+    pub fn is_affine(&self) -> bool {
+        self.0[3] == [0.0, 0.0, 0.0, 1.0]
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut transposed = [[0.0; 4]; 4];
+        for (i, row) in self.0.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                transposed[j][i] = *value;
+            }
+        }
+        Mat4(transposed)
+    }
+
+    pub fn determinant(&self) -> f64 {
+        // expand along the bottom row, which is usually (0, 0, 0, 1)
+        let m = &self.0;
+        (0..4)
+            .filter(|&j| m[3][j] != 0.0)
+            .map(|j| {
+                let mut minor = [[0.0; 3]; 3];
+                for (i, row) in minor.iter_mut().enumerate() {
+                    let columns = (0..4).filter(|&c| c != j);
+                    for (value, c) in row.iter_mut().zip(columns) {
+                        *value = m[i][c];
+                    }
+                }
+                let sign = if (3 + j) % 2 == 0 { 1.0 } else { -1.0 };
+                sign * m[3][j] * Mat3(minor).determinant()
+            })
+            .sum()
+    }
+
+    /// Returns the inverse, or None if the matrix is singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        // Gauss-Jordan elimination with partial pivoting
+        let mut m = self.0;
+        let mut inverse = Mat4::identity().0;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))
+                .unwrap();
+            if m[pivot][column] == 0.0 {
+                return None;
+            }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+            let scale = 1.0 / m[column][column];
+            for j in 0..4 {
+                m[column][j] *= scale;
+                inverse[column][j] *= scale;
+            }
+            for row in 0..4 {
+                let factor = m[row][column];
+                if row != column && factor != 0.0 {
+                    for j in 0..4 {
+                        m[row][j] -= factor * m[column][j];
+                        inverse[row][j] -= factor * inverse[column][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4(inverse))
+    }
+
+    /// Transforms a point, dividing through by w if the matrix is projective.
+    pub fn transform_point(&self, p: Point3) -> Point3 {
+        let m = &self.0;
+        let &[x, y, z] = &p.0 .0;
+        let row = |i: usize| m[i][0] * x + m[i][1] * y + m[i][2] * z + m[i][3];
+        let w = row(3);
+        if w == 1.0 {
+            Point3::new(row(0), row(1), row(2))
+        } else {
+            Point3::new(row(0) / w, row(1) / w, row(2) / w)
+        }
+    }
+
+    /// Transforms a direction, which ignores the translation.
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.linear() * v
+    }
+}
+
+impl<T> Mul<T> for Mat3
+where
+    T: Borrow<Mat3>,
+{
+    type Output = Mat3;
+
+    fn mul(self, rhs: T) -> Mat3 {
+        let rhs = rhs.borrow();
+        let mut product = [[0.0; 3]; 3];
+        for (i, row) in product.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Mat3(product)
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        &self * rhs
+    }
+}
+
+impl Mul<Vec3> for &Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Vec3 {
+        let dot = |i: usize| (0..3).map(|k| self.0[i][k] * rhs.0[k]).sum();
+        Vec3::new(dot(0), dot(1), dot(2))
+    }
+}
+
+impl Mul<f64> for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: f64) -> Mat3 {
+        Mat3(self.0.map(|row| row.map(|value| value * rhs)))
+    }
+}
+
+impl<T> Add<T> for Mat3
+where
+    T: Borrow<Mat3>,
+{
+    type Output = Mat3;
+
+    fn add(self, rhs: T) -> Mat3 {
+        let mut sum = self.0;
+        for (row, other) in sum.iter_mut().zip(rhs.borrow().0.iter()) {
+            for (value, other) in row.iter_mut().zip(other.iter()) {
+                *value += other;
+            }
+        }
+        Mat3(sum)
+    }
+}
+
+impl<T> Sub<T> for Mat3
+where
+    T: Borrow<Mat3>,
+{
+    type Output = Mat3;
+
+    fn sub(self, rhs: T) -> Mat3 {
+        self + -*rhs.borrow()
+    }
+}
+
+impl Neg for Mat3 {
+    type Output = Mat3;
+
+    fn neg(self) -> Mat3 {
+        self * -1.0
+    }
+}
+
+impl<T> Mul<T> for Mat4
+where
+    T: Borrow<Mat4>,
+{
+    type Output = Mat4;
+
+    fn mul(self, rhs: T) -> Mat4 {
+        let rhs = rhs.borrow();
+        let mut product = [[0.0; 4]; 4];
+        for (i, row) in product.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Mat4(product)
+    }
+}
+
+impl Mul<f64> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: f64) -> Mat4 {
+        Mat4(self.0.map(|row| row.map(|value| value * rhs)))
+    }
+}
+
+impl<T> Add<T> for Mat4
+where
+    T: Borrow<Mat4>,
+{
+    type Output = Mat4;
+
+    fn add(self, rhs: T) -> Mat4 {
+        let mut sum = self.0;
+        for (row, other) in sum.iter_mut().zip(rhs.borrow().0.iter()) {
+            for (value, other) in row.iter_mut().zip(other.iter()) {
+                *value += other;
+            }
+        }
+        Mat4(sum)
+    }
+}
+
+impl<T> Sub<T> for Mat4
+where
+    T: Borrow<Mat4>,
+{
+    type Output = Mat4;
+
+    fn sub(self, rhs: T) -> Mat4 {
+        self + -*rhs.borrow()
+    }
+}
+
+impl Neg for Mat4 {
+    type Output = Mat4;
+
+    fn neg(self) -> Mat4 {
+        self * -1.0
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::op_ref)]
+mod tests {
+    use super::*;
+
+    fn assert_mat4_near(a: &Mat4, b: &Mat4) {
+        let difference = (*a - b)
+            .0
+            .iter()
+            .flatten()
+            .fold(0.0, |m: f64, v| m.max(v.abs()));
+        assert!(difference < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    fn sample_mat3() -> Mat3 {
+        Mat3([[2.0, -1.0, 0.5], [0.0, 3.0, 1.0], [1.0, 4.0, -2.0]])
+    }
+
+    fn sample_mat4() -> Mat4 {
+        Mat4([
+            [2.0, 0.0, 1.0, 3.0],
+            [1.0, -1.0, 0.0, 2.0],
+            [0.0, 4.0, 1.0, -1.0],
+            [0.5, 0.0, 0.0, 2.0],
+        ])
+    }
+
+    #[test]
+    fn mat3_basics() {
+        let m = sample_mat3();
+        assert_eq!(m * Mat3::identity(), m);
+        assert_eq!(Mat3::identity() * &m, m);
+        assert_eq!(m.transpose().transpose(), m);
+        assert_eq!(m.row(1), Vec3::new(0.0, 3.0, 1.0));
+        assert_eq!(m.column(1), Vec3::new(-1.0, 3.0, 4.0));
+        assert_eq!(Mat3::from_columns(m.column(0), m.column(1), m.column(2)), m);
+        assert_eq!(m * Vec3::new(1.0, 0.0, 0.0), m.column(0));
+        assert_eq!(m + m, m * 2.0);
+        assert_eq!(m - m, Mat3::diagonal(Vec3::fill(0.0)));
+        assert_eq!(Mat3::diagonal(Vec3::new(1.0, 2.0, 3.0)).determinant(), 6.0);
+    }
+
+    #[test]
+    fn mat3_inverse() {
+        let m = sample_mat3();
+        let product = m * m.inverse().unwrap();
+        for i in 0..3 {
+            assert!((product.row(i) - Mat3::identity().row(i)).length() < 1e-12);
+        }
+        let singular = Mat3([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 1.0, 1.0]]);
+        assert_eq!(singular.determinant(), 0.0);
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn mat4_determinant_and_inverse() {
+        let m = sample_mat4();
+        // worked out by hand along the bottom row
+        assert!((m.determinant() - 2.0).abs() < 1e-12);
+        assert_mat4_near(&(m * m.inverse().unwrap()), &Mat4::identity());
+        assert_mat4_near(&(m.inverse().unwrap() * m), &Mat4::identity());
+        assert_eq!(m.transpose().transpose(), m);
+
+        let mut singular = m;
+        singular.0[2] = singular.0[0];
+        assert!(singular.inverse().is_none());
+    }
+
+    #[test]
+    fn mat4_affine_parts() {
+        let linear = sample_mat3();
+        let m = Mat4::from_linear(&linear, Vec3::new(1.0, 2.0, 3.0));
+        assert!(m.is_affine());
+        assert!(!sample_mat4().is_affine());
+        assert_eq!(m.linear(), linear);
+        assert_eq!(m.translation(), Vec3::new(1.0, 2.0, 3.0));
+        assert!((m.determinant() - linear.determinant()).abs() < 1e-12);
+        assert_eq!(
+            m.transform_point(Point3::origin()),
+            Point3::new(1.0, 2.0, 3.0)
+        );
+        assert_eq!(
+            m.transform_vector(Vec3::new(0.0, 1.0, 0.0)),
+            linear.column(1)
+        );
+    }
+
+    #[test]
+    fn projective_points_are_divided_by_w() {
+        let mut m = Mat4::identity();
+        m.0[3] = [0.0, 0.0, 1.0, 0.0];
+        assert_eq!(
+            m.transform_point(Point3::new(2.0, 4.0, 2.0)),
+            Point3::new(1.0, 2.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use super::{normalize, Vec3};
+use std::borrow::Borrow;
+use std::ops::{Add, AddAssign, Neg, Sub, SubAssign};
+
+/// A position in space.
+/// Points can be offset by vectors, and subtracting two gives the vector between them,
+/// but adding two points has no meaning and doesn't compile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point3(pub Vec3);
+
+/// A surface normal. It transforms differently to a direction, but can be used as one
+/// anywhere a Vec3 is borrowed, such as in dot products and reflections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normal3(pub Vec3);
+
+impl Point3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Point3 {
+        Point3(Vec3::new(x, y, z))
+    }
+
+    pub fn origin() -> Point3 {
+        Point3(Vec3::fill(0.0))
+    }
+
+    pub fn x(&self) -> f64 {
+        self.0.x()
+    }
+
+    pub fn y(&self) -> f64 {
+        self.0.y()
+    }
+
+    pub fn z(&self) -> f64 {
+        self.0.z()
+    }
+
+    pub fn distance(&self, other: Point3) -> f64 {
+        (*self - other).length()
+    }
+}
+
+impl Normal3 {
+    pub fn new(x: f64, y: f64, z: f64) -> Normal3 {
+        Normal3(Vec3::new(x, y, z))
+    }
+
+    pub fn x(&self) -> f64 {
+        self.0.x()
+    }
+
+    pub fn y(&self) -> f64 {
+        self.0.y()
+    }
+
+    pub fn z(&self) -> f64 {
+        self.0.z()
+    }
+
+    pub fn normalize(&self) -> Normal3 {
+        Normal3(normalize(self.0))
+    }
+}
+
+impl From<Vec3> for Point3 {
+    fn from(v: Vec3) -> Point3 {
+        Point3(v)
+    }
+}
+
+impl From<Point3> for Vec3 {
+    fn from(p: Point3) -> Vec3 {
+        p.0
+    }
+}
+
+impl From<Vec3> for Normal3 {
+    fn from(v: Vec3) -> Normal3 {
+        Normal3(v)
+    }
+}
+
+impl From<Normal3> for Vec3 {
+    fn from(n: Normal3) -> Vec3 {
+        n.0
+    }
+}
+
+impl Borrow<Vec3> for Normal3 {
+    fn borrow(&self) -> &Vec3 {
+        &self.0
+    }
+}
+
+impl Borrow<Vec3> for &Normal3 {
+    fn borrow(&self) -> &Vec3 {
+        &self.0
+    }
+}
+
+impl<T> Add<T> for Point3
+where
+    T: Borrow<Vec3>,
+{
+    type Output = Point3;
+
+    // Moves the point by a vector.
+    fn add(self, rhs: T) -> Point3 {
+        Point3(self.0 + rhs)
+    }
+}
+
+impl<T> AddAssign<T> for Point3
+where
+    T: Borrow<Vec3>,
+{
+    fn add_assign(&mut self, rhs: T) {
+        self.0 = self.0 + rhs;
+    }
+}
+
+impl<T> Sub<T> for Point3
+where
+    T: Borrow<Vec3>,
+{
+    type Output = Point3;
+
+    fn sub(self, rhs: T) -> Point3 {
+        Point3(self.0 - rhs)
+    }
+}
+
+impl<T> SubAssign<T> for Point3
+where
+    T: Borrow<Vec3>,
+{
+    fn sub_assign(&mut self, rhs: T) {
+        self.0 = self.0 - rhs;
+    }
+}
+
+/// The vector from rhs to self.
+impl Sub<Point3> for Point3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Point3) -> Vec3 {
+        self.0 - rhs.0
+    }
+}
+
+impl Neg for Normal3 {
+    type Output = Normal3;
+
+    fn neg(self) -> Normal3 {
+        Normal3(-self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dot;
+    use super::*;
+
+    #[test]
+    fn points_and_vectors() {
+        let a = Point3::new(1.0, 2.0, 3.0);
+        let b = Point3::new(4.0, 6.0, 3.0);
+        assert_eq!(b - a, Vec3::new(3.0, 4.0, 0.0));
+        assert_eq!(a + (b - a), b);
+        assert_eq!(b - Vec3::new(3.0, 4.0, 0.0), a);
+        assert_eq!(a.distance(b), 5.0);
+
+        let mut c = a;
+        c += Vec3::fill(1.0);
+        assert_eq!(c, Point3::new(2.0, 3.0, 4.0));
+        assert_eq!(Vec3::from(c), Vec3::new(2.0, 3.0, 4.0));
+    }
+
+    #[test]
+    fn normals_act_as_directions() {
+        let n = Normal3::new(0.0, 2.0, 0.0);
+        assert_eq!(dot(Vec3::new(1.0, 1.0, 0.0), n), 2.0);
+        assert_eq!(n.normalize(), Normal3::new(0.0, 1.0, 0.0));
+        assert_eq!(Point3::origin() + n, Point3::new(0.0, 2.0, 0.0));
+        assert_eq!(-n, Normal3::new(0.0, -2.0, 0.0));
+    }
+}
@@ -0,0 +1,266 @@
+use super::{cross, dot, normalize, Mat3, Vec3};
+use std::ops::{Add, Mul, Neg};
+
+/// A quaternion w + xi + yj + zk. Unit quaternions represent rotations.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quat {
+    pub fn new(w: f64, x: f64, y: f64, z: f64) -> Quat {
+        Quat { w, x, y, z }
+    }
+
+    pub fn identity() -> Quat {
+        Quat::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    /// A rotation counter-clockwise by the given number of degrees about an axis.
+    pub fn from_axis_angle(axis: Vec3, degrees: f64) -> Quat {
+        let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+        Quat::from_parts(cos, normalize(axis) * sin)
+    }
+
+    /// The rotation described by an orthonormal matrix with a positive determinant.
+    pub fn from_mat3(m: &Mat3) -> Quat {
+        let m = &m.0;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        // work from the largest component to keep precision
+        let q = if trace > 0.0 {
+            let s = 2.0 * f64::sqrt(trace + 1.0);
+            Quat::new(
+                s / 4.0,
+                (m[2][1] - m[1][2]) / s,
+                (m[0][2] - m[2][0]) / s,
+                (m[1][0] - m[0][1]) / s,
+            )
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = 2.0 * f64::sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]);
+            Quat::new(
+                (m[2][1] - m[1][2]) / s,
+                s / 4.0,
+                (m[0][1] + m[1][0]) / s,
+                (m[0][2] + m[2][0]) / s,
+            )
+        } else if m[1][1] > m[2][2] {
+            let s = 2.0 * f64::sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]);
+            Quat::new(
+                (m[0][2] - m[2][0]) / s,
+                (m[0][1] + m[1][0]) / s,
+                s / 4.0,
+                (m[1][2] + m[2][1]) / s,
+            )
+        } else {
+            let s = 2.0 * f64::sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]);
+            Quat::new(
+                (m[1][0] - m[0][1]) / s,
+                (m[0][2] + m[2][0]) / s,
+                (m[1][2] + m[2][1]) / s,
+                s / 4.0,
+            )
+        };
+        q.normalize()
+    }
+
+    fn from_parts(w: f64, v: Vec3) -> Quat {
+        Quat::new(w, v.x(), v.y(), v.z())
+    }
+
+    /// The vector (imaginary) part.
+    pub fn vector(&self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn dot(&self, other: &Quat) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn length(&self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quat {
+        *self * (1.0 / self.length())
+    }
+
+    pub fn conjugate(&self) -> Quat {
+        Quat::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// The inverse rotation, or the multiplicative inverse of any non-zero quaternion.
+    pub fn inverse(&self) -> Quat {
+        self.conjugate() * (1.0 / self.dot(self))
+    }
+
+    /// Rotates a vector by this unit quaternion.
+    pub fn rotate(&self, v: Vec3) -> Vec3 {
+        // v + 2w(q x v) + 2q x (q x v), expanded from q v q*
+        let q = self.vector();
+        let t = cross(q, v) * 2.0;
+        v + t * self.w + cross(q, t)
+    }
+
+    pub fn to_mat3(&self) -> Mat3 {
+        let Quat { w, x, y, z } = *self;
+        Mat3([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+            ],
+        ])
+    }
+
+    /// Interpolates between two rotations at constant angular speed, taking the shorter way round.
+    pub fn slerp(&self, other: &Quat, t: f64) -> Quat {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+        if cos < 0.0 {
+            // q and -q are the same rotation
+            cos = -cos;
+            other = -other;
+        }
+        if cos > 0.9995 {
+            // nearly parallel, where a straight line is accurate and avoids dividing by ~0
+            return (*self * (1.0 - t) + other * t).normalize();
+        }
+        let angle = cos.acos();
+        let sin = angle.sin();
+        *self * (f64::sin((1.0 - t) * angle) / sin) + other * (f64::sin(t * angle) / sin)
+    }
+}
+
+/// The Hamilton product: (a * b) rotates by b first, then a.
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: Quat) -> Quat {
+        let (a, b) = (self.vector(), rhs.vector());
+        Quat::from_parts(
+            self.w * rhs.w - dot(a, b),
+            b * self.w + a * rhs.w + cross(a, b),
+        )
+    }
+}
+
+impl Mul<f64> for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: f64) -> Quat {
+        Quat::new(self.w * rhs, self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Add for Quat {
+    type Output = Quat;
+
+    fn add(self, rhs: Quat) -> Quat {
+        Quat::new(
+            self.w + rhs.w,
+            self.x + rhs.x,
+            self.y + rhs.y,
+            self.z + rhs.z,
+        )
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Quat {
+        self * -1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    fn same_rotation(a: &Quat, b: &Quat) -> bool {
+        (a.dot(b).abs() - 1.0).abs() < 1e-12
+    }
+
+    #[test]
+    fn rotating_vectors() {
+        let quarter_turn = Quat::from_axis_angle(Vec3::new(0.0, 0.0, 2.0), 90.0);
+        assert_near(
+            quarter_turn.rotate(Vec3::new(1.0, 0.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert_near(
+            quarter_turn.inverse().rotate(Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(1.0, 0.0, 0.0),
+        );
+        assert_eq!(
+            Quat::identity().rotate(Vec3::new(1.0, 2.0, 3.0)),
+            Vec3::new(1.0, 2.0, 3.0)
+        );
+    }
+
+    #[test]
+    fn products_compose_rotations() {
+        let a = Quat::from_axis_angle(Vec3::new(1.0, 0.0, 0.0), 40.0);
+        let b = Quat::from_axis_angle(Vec3::new(0.0, 1.0, 1.0), -75.0);
+        let v = Vec3::new(0.3, -2.0, 1.1);
+        assert_near((a * b).rotate(v), a.rotate(b.rotate(v)));
+        assert!(same_rotation(&(a * a.conjugate()), &Quat::identity()));
+        // a quaternion and its negation are the same rotation
+        assert_near((-a).rotate(v), a.rotate(v));
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let v = Vec3::new(0.5, 1.5, -2.5);
+        // angles that exercise each branch of the matrix conversion
+        for (axis, degrees) in &[
+            (Vec3::new(1.0, 2.0, 3.0), 30.0),
+            (Vec3::new(1.0, 0.0, 0.0), 170.0),
+            (Vec3::new(0.0, 1.0, 0.0), 170.0),
+            (Vec3::new(0.0, 0.0, 1.0), 180.0),
+        ] {
+            let q = Quat::from_axis_angle(*axis, *degrees);
+            let m = q.to_mat3();
+            assert_near(m * v, q.rotate(v));
+            assert!((m.determinant() - 1.0).abs() < 1e-12);
+            assert!(same_rotation(&Quat::from_mat3(&m), &q), "{:?}", axis);
+        }
+    }
+
+    #[test]
+    fn slerp_moves_at_constant_speed() {
+        let axis = Vec3::new(0.0, 1.0, 0.0);
+        let a = Quat::from_axis_angle(axis, 10.0);
+        let b = Quat::from_axis_angle(axis, 110.0);
+        assert!(same_rotation(&a.slerp(&b, 0.0), &a));
+        assert!(same_rotation(&a.slerp(&b, 1.0), &b));
+        assert!(same_rotation(
+            &a.slerp(&b, 0.25),
+            &Quat::from_axis_angle(axis, 35.0)
+        ));
+        // takes the short way even when the signs differ
+        assert!(same_rotation(
+            &a.slerp(&-b, 0.5),
+            &Quat::from_axis_angle(axis, 60.0)
+        ));
+        let close = Quat::from_axis_angle(axis, 10.001);
+        assert!((a.slerp(&close, 0.5).length() - 1.0).abs() < 1e-12);
+    }
+}
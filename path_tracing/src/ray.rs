@@ -1,26 +1,41 @@
 use super::vector::*;
+use super::Point3;
 
 pub struct Ray {
-    origin: Vec3,
+    origin: Point3,
     direction: Vec3,
+    time: f64,
 }
 
 impl Ray {
-    pub fn new(origin: Vec3, direction: Vec3) -> Ray {
-        Ray { origin, direction }
+    pub fn new<P: Into<Point3>>(origin: P, direction: Vec3) -> Ray {
+        Ray::at_time(origin, direction, 0.0)
     }
 
-    pub fn point_at_parameter(&self, t: f64) -> Vec3 {
-        &self.origin + (&self.direction * t)
+    /// A ray cast at the given moment while the shutter is open, for motion blur.
+    pub fn at_time<P: Into<Point3>>(origin: P, direction: Vec3, time: f64) -> Ray {
+        Ray {
+            origin: origin.into(),
+            direction,
+            time,
+        }
     }
 
-    pub fn origin(&self) -> &Vec3 {
+    pub fn point_at_parameter(&self, t: f64) -> Point3 {
+        self.origin + (self.direction * t)
+    }
+
+    pub fn origin(&self) -> &Point3 {
         &self.origin
     }
 
     pub fn direction(&self) -> &Vec3 {
         &self.direction
     }
+
+    pub fn time(&self) -> f64 {
+        self.time
+    }
 }
 
 #[cfg(test)]
@@ -30,6 +45,6 @@ mod tests {
     #[test]
     fn point_along_ray() {
         let ray = Ray::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(1.0, 0.0, 0.0));
-        assert_eq!(ray.point_at_parameter(0.5), Vec3::new(1.5, 1.0, 1.0));
+        assert_eq!(ray.point_at_parameter(0.5), Point3::new(1.5, 1.0, 1.0));
     }
 }
@@ -0,0 +1,397 @@
+use super::stats::count_intersection_test;
+use super::{cross, dot, normalize, Aabb, Normal3, Point3, Ray, Vec3};
+use super::{HitRecord, Hitable, Scattering};
+
+/// Where a ray crosses the plane where axis k equals the given value, if that point
+/// lies within [a0, a1] on axis a and [b0, b1] on axis b.
+/// Returns the ray parameter and the point's (u, v) position across the rectangle.
+/// Rectangles with no area are never hit, rather than giving UVs that divide by zero.
+fn hit_axis_rect(
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+    (a, a0, a1): (usize, f64, f64),
+    (b, b0, b1): (usize, f64, f64),
+    (k, value): (usize, f64),
+) -> Option<(f64, f64, f64)> {
+    if !(a0 < a1 && b0 < b1) {
+        return None;
+    }
+    count_intersection_test();
+    let origin = ray.origin().0;
+    let t = (value - origin.0[k]) / ray.direction().0[k];
+    if !(t > t_min && t < t_max) {
+        return None;
+    }
+    let p = ray.point_at_parameter(t).0;
+    let (pa, pb) = (p.0[a], p.0[b]);
+    if pa < a0 || pa > a1 || pb < b0 || pb > b1 {
+        return None;
+    }
+    Some((t, (pa - a0) / (a1 - a0), (pb - b0) / (b1 - b0)))
+}
+
+/// Builds the hit record for an axis-aligned rectangle facing along +k or -k.
+fn axis_rect_record<'a>(
+    ray: &Ray,
+    (t, u, v): (f64, f64, f64),
+    k: usize,
+    sign: f64,
+    material: &'a dyn Scattering,
+) -> HitRecord<'a> {
+    let mut normal = Vec3::fill(0.0);
+    normal.0[k] = sign;
+    HitRecord {
+        t,
+        p: ray.point_at_parameter(t),
+        normal: Normal3(normal),
+        u,
+        v,
+        material,
+    }
+}
+
+/// Bounds an axis-aligned rectangle, padded a little along k so the box isn't flat.
+fn axis_rect_box(
+    (a, a0, a1): (usize, f64, f64),
+    (b, b0, b1): (usize, f64, f64),
+    (k, value): (usize, f64),
+) -> Aabb {
+    let (mut min, mut max) = (Vec3::fill(0.0), Vec3::fill(0.0));
+    min.0[a] = a0;
+    max.0[a] = a1;
+    min.0[b] = b0;
+    max.0[b] = b1;
+    min.0[k] = value - 0.0001;
+    max.0[k] = value + 0.0001;
+    Aabb::new(Point3(min), Point3(max))
+}
+
+/// A rectangle parallel to the xy plane at z = k, facing +z.
+/// Wrap it in FlipNormals to face -z.
+pub struct XYRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub y0: f64,
+    pub y1: f64,
+    pub k: f64,
+    pub material: Box<dyn Scattering + Sync>,
+}
+
+impl Hitable for XYRect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_axis_rect(
+            ray,
+            t_min,
+            t_max,
+            (0, self.x0, self.x1),
+            (1, self.y0, self.y1),
+            (2, self.k),
+        )
+        .map(|hit| axis_rect_record(ray, hit, 2, 1.0, &*self.material))
+    }
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        Some(axis_rect_box(
+            (0, self.x0, self.x1),
+            (1, self.y0, self.y1),
+            (2, self.k),
+        ))
+    }
+}
+
+/// A rectangle parallel to the xz plane at y = k, facing +y.
+/// Wrap it in FlipNormals to face -y.
+pub struct XZRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Box<dyn Scattering + Sync>,
+}
+
+impl Hitable for XZRect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_axis_rect(
+            ray,
+            t_min,
+            t_max,
+            (0, self.x0, self.x1),
+            (2, self.z0, self.z1),
+            (1, self.k),
+        )
+        .map(|hit| axis_rect_record(ray, hit, 1, 1.0, &*self.material))
+    }
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        Some(axis_rect_box(
+            (0, self.x0, self.x1),
+            (2, self.z0, self.z1),
+            (1, self.k),
+        ))
+    }
+}
+
+/// A rectangle parallel to the yz plane at x = k, facing +x.
+/// Wrap it in FlipNormals to face -x.
+pub struct YZRect {
+    pub y0: f64,
+    pub y1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    pub material: Box<dyn Scattering + Sync>,
+}
+
+impl Hitable for YZRect {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_axis_rect(
+            ray,
+            t_min,
+            t_max,
+            (1, self.y0, self.y1),
+            (2, self.z0, self.z1),
+            (0, self.k),
+        )
+        .map(|hit| axis_rect_record(ray, hit, 0, 1.0, &*self.material))
+    }
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        Some(axis_rect_box(
+            (1, self.y0, self.y1),
+            (2, self.z0, self.z1),
+            (0, self.k),
+        ))
+    }
+}
+
+/// An infinite plane through a point, facing along its normal.
+/// Its UVs are distances in scene units along two directions in the plane,
+/// so textures repeat rather than stretch.
+pub struct Plane {
+    pub point: Point3,
+    pub normal: Normal3,
+    pub material: Box<dyn Scattering + Sync>,
+}
+
+impl Hitable for Plane {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        count_intersection_test();
+        let normal = normalize(self.normal);
+        let denominator = dot(ray.direction(), normal);
+        if denominator == 0.0 {
+            return None;
+        }
+        let t = dot(self.point - *ray.origin(), normal) / denominator;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+        let p = ray.point_at_parameter(t);
+        // any axis not parallel to the normal gives a basis in the plane
+        let axis = if normal.x().abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = normalize(cross(axis, normal));
+        let bitangent = cross(normal, tangent);
+        let offset = p - self.point;
+        Some(HitRecord {
+            t,
+            p,
+            normal: Normal3(normal),
+            u: dot(offset, tangent),
+            v: dot(offset, bitangent),
+            material: &*self.material,
+        })
+    }
+}
+
+/// Turns a surface around so its normals point the other way, e.g. for the walls of a
+/// room seen from inside. Rectangles and planes have one facing, which is the side
+/// their normal points to whichever side a ray hits them from.
+pub struct FlipNormals<H: Hitable>(pub H);
+
+impl<H: Hitable> Hitable for FlipNormals<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let mut hit = self.0.hit(ray, t_min, t_max)?;
+        hit.normal = Normal3(-hit.normal.0);
+        Some(hit)
+    }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        self.0.bounding_box(time0, time1)
+    }
+}
+
+/// An axis-aligned box between two corners, made of six rectangles facing outward
+pub struct Cuboid {
+    pub min: Point3,
+    pub max: Point3,
+    pub material: Box<dyn Scattering + Sync>,
+}
+
+impl Hitable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let (min, max) = (self.min.0 .0, self.max.0 .0);
+        let mut closest = t_max;
+        let mut found = None;
+        for k in 0..3 {
+            // the other two axes, in the order the matching rectangle uses them
+            let (a, b) = match k {
+                0 => (1, 2),
+                1 => (0, 2),
+                _ => (0, 1),
+            };
+            for &(value, sign) in &[(min[k], -1.0), (max[k], 1.0)] {
+                if let Some(hit) = hit_axis_rect(
+                    ray,
+                    t_min,
+                    closest,
+                    (a, min[a], max[a]),
+                    (b, min[b], max[b]),
+                    (k, value),
+                ) {
+                    closest = hit.0;
+                    found = Some(axis_rect_record(ray, hit, k, sign, &*self.material));
+                }
+            }
+        }
+        found
+    }
+
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{Color, Lambertian};
+    use super::*;
+
+    fn material() -> Box<dyn Scattering + Sync> {
+        Box::new(Lambertian {
+            albedo: Color::fill(0.5),
+        })
+    }
+
+    #[test]
+    fn rectangles() {
+        let rect = XZRect {
+            x0: -1.0,
+            x1: 1.0,
+            z0: 0.0,
+            z1: 4.0,
+            k: 2.0,
+            material: material(),
+        };
+        let down = Ray::new(Vec3::new(0.5, 5.0, 1.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = rect.hit(&down, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.t, 3.0);
+        assert_eq!(hit.p, Point3::new(0.5, 2.0, 1.0));
+        assert_eq!(hit.normal, Normal3::new(0.0, 1.0, 0.0));
+        assert_eq!((hit.u, hit.v), (0.75, 0.25));
+
+        let beside = Ray::new(Vec3::new(1.5, 5.0, 1.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(rect.hit(&beside, 0.0, f64::MAX).is_none());
+        assert!(rect.hit(&down, 0.0, 2.0).is_none());
+        let parallel = Ray::new(Vec3::new(0.0, 5.0, 1.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(rect.hit(&parallel, 0.0, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn flipped_surfaces_face_the_other_way() {
+        let rect = XYRect {
+            x0: -1.0,
+            x1: 1.0,
+            y0: -1.0,
+            y1: 1.0,
+            k: 0.0,
+            material: material(),
+        };
+        // hit from behind, the rectangle still faces +z
+        let from_behind = Ray::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 1.0));
+        let hit = rect.hit(&from_behind, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.normal, Normal3::new(0.0, 0.0, 1.0));
+
+        let flipped = FlipNormals(rect);
+        let hit = flipped.hit(&from_behind, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.normal, Normal3::new(0.0, 0.0, -1.0));
+        assert_eq!((hit.u, hit.v), (0.5, 0.5));
+        assert!(flipped.bounding_box(0.0, 1.0).is_some());
+
+        let ceiling = FlipNormals(Plane {
+            point: Point3::new(0.0, 3.0, 0.0),
+            normal: Normal3::new(0.0, 1.0, 0.0),
+            material: material(),
+        });
+        let up = Ray::new(Vec3::fill(0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = ceiling.hit(&up, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.normal, Normal3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn degenerate_rectangles_are_never_hit() {
+        let line = YZRect {
+            y0: 1.0,
+            y1: 1.0,
+            z0: -1.0,
+            z1: 1.0,
+            k: 0.0,
+            material: material(),
+        };
+        let across = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!(line.hit(&across, 0.0, f64::MAX).is_none());
+
+        let point = XZRect {
+            x0: 0.0,
+            x1: 0.0,
+            z0: 0.0,
+            z1: 0.0,
+            k: 0.0,
+            material: material(),
+        };
+        let down = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!(point.hit(&down, 0.0, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn plane_uvs_measure_distance() {
+        let plane = Plane {
+            point: Point3::origin(),
+            normal: Normal3::new(0.0, 2.0, 0.0),
+            material: material(),
+        };
+        let a = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let b = Ray::new(Vec3::new(3.0, 1.0, 4.0), Vec3::new(0.0, -1.0, 0.0));
+        let a = plane.hit(&a, 0.0, f64::MAX).unwrap();
+        let b = plane.hit(&b, 0.0, f64::MAX).unwrap();
+        assert_eq!(a.normal, Normal3::new(0.0, 1.0, 0.0));
+        assert_eq!((a.u, a.v), (0.0, 0.0));
+        let distance = f64::sqrt((b.u - a.u).powi(2) + (b.v - a.v).powi(2));
+        assert!((distance - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn cuboid_faces_point_outward() {
+        let cuboid = Cuboid {
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 2.0, 1.0),
+            material: material(),
+        };
+        let from_front = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let hit = cuboid.hit(&from_front, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert_eq!(hit.normal, Normal3::new(0.0, 0.0, 1.0));
+
+        let from_below = Ray::new(Vec3::new(0.0, -3.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let hit = cuboid.hit(&from_below, 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.p, Point3::new(0.0, -1.0, 0.0));
+        assert_eq!(hit.normal, Normal3::new(0.0, -1.0, 0.0));
+
+        // from inside, the far face is hit
+        let inside = Ray::new(Vec3::fill(0.0), Vec3::new(1.0, 0.0, 0.0));
+        let hit = cuboid.hit(&inside, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.p, Point3::new(1.0, 0.0, 0.0));
+    }
+}
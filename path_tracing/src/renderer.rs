@@ -0,0 +1,791 @@
+use super::hitable::*;
+use super::stats::{count_bounce_ray, count_camera_ray, count_shadow_ray, flush_stats};
+use super::{
+    mix, normalize, rand, seed_random, tiles, Camera, CancellationToken, Checkpoint, Color, Film,
+    Filter, Ray, Tile, TileOrder, World,
+};
+use rayon::prelude::*;
+use std::io;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+fn color(world: &World, ray: Ray, depth: u8) -> Color {
+    if let Some(hit) = world.hit(&ray, 0.001, f64::MAX) {
+        // return (hit.normal + 1.0) * 0.5;
+        // recurse until you bounce off into the sky
+        if depth < 50 {
+            let direct = direct_light(world, &ray, &hit);
+            if let Some(reflection) = hit.material.scatter(&ray, &hit) {
+                count_bounce_ray();
+                // return (*reflection.ray.direction() + 1.0) * 0.5;
+                return direct + color(world, reflection.ray, depth + 1) * reflection.attenuation;
+            } else {
+                return direct;
+            }
+        }
+    }
+
+    let unit_direction = normalize(ray.direction());
+    let t = 0.5 * (unit_direction.y() + 1.0);
+    mix(Color::new(1.0, 1.0, 1.0), Color::new(0.5, 0.7, 1.0), t)
+}
+
+/// Light reaching the hit straight from the world's lights, with a shadow ray to each
+/// light the material reflects towards the viewer.
+fn direct_light(world: &World, ray: &Ray, hit: &HitRecord) -> Color {
+    let mut total = Color::black();
+    for light in world.lights() {
+        let sample = match light.sample(hit.p) {
+            Some(sample) => sample,
+            None => continue,
+        };
+        let reflectance = hit.material.reflectance(ray, hit, sample.direction);
+        if reflectance == Color::black() {
+            continue;
+        }
+        count_shadow_ray();
+        let shadow = Ray::at_time(hit.p, sample.direction, ray.time());
+        if world.hit(&shadow, 0.001, sample.distance).is_none() {
+            total += reflectance * sample.irradiance;
+        }
+    }
+    total
+}
+
+/// Samples gathered by all the passes rendered so far.
+struct Accumulation {
+    film: Film,
+    passes: u32,
+    samples_per_pixel: u32,
+    /// Samples from a pass that was cancelled before all of its tiles finished.
+    /// They show in the image but are left out of checkpoints, which hold whole passes.
+    interrupted: Option<Film>,
+}
+
+/// Renders a scene in passes, adding each pass of samples to an accumulation buffer.
+/// The current image can be read between (or during) passes, so previews appear
+/// quickly and rendering can stop whenever the image looks good enough.
+pub struct ProgressiveRenderer<'a> {
+    world: &'a World,
+    camera: &'a dyn Camera,
+    filter: &'a (dyn Filter + Sync),
+    rows: u32,
+    columns: u32,
+    seed: u64,
+    tile_size: u32,
+    tile_order: TileOrder,
+    region: Tile,
+    exposure: f64,
+    cancellation: CancellationToken,
+    accumulation: Mutex<Accumulation>,
+}
+
+impl<'a> ProgressiveRenderer<'a> {
+    pub fn new(
+        world: &'a World,
+        camera: &'a dyn Camera,
+        rows: u32,
+        columns: u32,
+        filter: &'a (dyn Filter + Sync),
+    ) -> ProgressiveRenderer<'a> {
+        ProgressiveRenderer {
+            world,
+            camera,
+            filter,
+            rows,
+            columns,
+            seed: 0,
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            region: Tile {
+                x: 0,
+                y: 0,
+                width: columns,
+                height: rows,
+            },
+            exposure: 1.0,
+            cancellation: CancellationToken::new(),
+            accumulation: Mutex::new(Accumulation {
+                film: Film::new(columns, rows),
+                passes: 0,
+                samples_per_pixel: 0,
+                interrupted: None,
+            }),
+        }
+    }
+
+    /// Sets the seed that the samples of every pass are drawn from.
+    /// Renders with the same seed and passes produce identical images.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Sets how passes are split into tiles and the order tiles are rendered in.
+    pub fn set_tiles(&mut self, tile_size: u32, tile_order: TileOrder) {
+        self.tile_size = tile_size;
+        self.tile_order = tile_order;
+    }
+
+    /// Restricts rendering to a rectangle of pixels, e.g. to re-render a problem area
+    /// with more samples. Pixels outside the region stay black.
+    /// Samples are also taken in a border as wide as the filter reaches, so the region's
+    /// edge pixels match a full render.
+    /// The region is clipped to the image; a region entirely outside it is an error.
+    pub fn set_region(&mut self, region: Tile) -> Result<(), String> {
+        let image = Tile {
+            x: 0,
+            y: 0,
+            width: self.columns,
+            height: self.rows,
+        };
+        match image.intersection(&region) {
+            Some(region) => {
+                self.region = region;
+                Ok(())
+            }
+            None => Err(format!(
+                "Region {:?} lies outside the {}x{} image",
+                region, self.columns, self.rows
+            )),
+        }
+    }
+
+    pub fn region(&self) -> Tile {
+        self.region
+    }
+
+    /// Scales the brightness of the image, e.g. by Exposure::multiplier.
+    /// Only the output is scaled, so it can be changed after rendering.
+    pub fn set_exposure(&mut self, multiplier: f64) {
+        self.exposure = multiplier;
+    }
+
+    /// Lets another thread stop the render by cancelling the token.
+    /// Once cancelled, the pass in progress stops at the next row of pixels
+    /// and no more passes are rendered.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = token;
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Renders another pass taking num_samples per pixel and adds it to the accumulated image.
+    /// Returns false if the render was cancelled before the pass finished.
+    pub fn render_pass(&self, num_samples: u32) -> bool {
+        self.render_pass_with_progress(num_samples, |_, _| {})
+    }
+
+    /// Renders another pass, calling on_tile as each tile finishes with the samples it gathered,
+    /// so callers can report progress or show a partial image while the pass runs.
+    /// The pass is added to the accumulated image once every tile is done.
+    /// If the render is cancelled first, the samples gathered so far still appear in the image
+    /// and false is returned.
+    pub fn render_pass_with_progress<F>(&self, num_samples: u32, on_tile: F) -> bool
+    where
+        F: Fn(&Tile, &Film) + Sync,
+    {
+        if self.is_cancelled() {
+            return false;
+        }
+
+        let pass = self.passes();
+        let tiles = self.tiles();
+        // threads pull tiles from the iterator as they free up, so tiles usually start
+        // in scheduling order, but par_bridge doesn't promise that or keep their results in order
+        let mut rendered: Vec<(usize, Film)> = tiles
+            .iter()
+            .enumerate()
+            .par_bridge()
+            .filter(|_| !self.is_cancelled())
+            .map(|(index, tile)| {
+                let film = self.render_tile(tile, pass, num_samples);
+                on_tile(tile, &film);
+                (index, film)
+            })
+            .collect();
+        // merge in a fixed order so floating point sums don't depend on thread timing
+        rendered.sort_by_key(|(index, _)| *index);
+
+        let films: Vec<Film> = rendered.into_iter().map(|(_, film)| film).collect();
+        if self.is_cancelled() {
+            let mut interrupted = Film::new(self.columns, self.rows);
+            for film in &films {
+                interrupted.merge(film);
+            }
+            self.accumulation.lock().unwrap().interrupted = Some(interrupted);
+            return false;
+        }
+
+        self.add_pass(&films, num_samples);
+        true
+    }
+
+    /// The tiles each pass is split into, in the order they are rendered.
+    pub fn tiles(&self) -> Vec<Tile> {
+        tiles(&self.sampled_region(), self.tile_size, self.tile_order)
+    }
+
+    /// The region grown by the pixels whose samples the filter splats into it.
+    pub(crate) fn sampled_region(&self) -> Tile {
+        // samples in a pixel lie within half a pixel of its center
+        let border = (self.filter.radius() - 0.5).ceil().max(0.0) as u32;
+        let x = self.region.x.saturating_sub(border);
+        let y = self.region.y.saturating_sub(border);
+        // the region lies within the image, so these sums can't overflow
+        let right = u32::min(self.columns, self.region.x + self.region.width + border);
+        let bottom = u32::min(self.rows, self.region.y + self.region.height + border);
+        Tile {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// Adds a pass rendered elsewhere (e.g. by other processes) to the accumulated image.
+    /// The films should be those rendered for each of the pass's tiles, in tile order,
+    /// so the image matches what render_pass would have produced.
+    pub fn add_pass(&self, films: &[Film], num_samples: u32) {
+        let mut accumulation = self.accumulation.lock().unwrap();
+        for film in films {
+            accumulation.film.merge(film);
+        }
+        accumulation.passes += 1;
+        accumulation.samples_per_pixel += num_samples;
+    }
+
+    /// Takes num_samples for every pixel of a tile, drawn from the given pass's samples.
+    /// The returned film extends past the tile as far as the filter splats samples.
+    pub fn render_tile(&self, tile: &Tile, pass: u32, num_samples: u32) -> Film {
+        let (rows, columns, filter) = (self.rows, self.columns, self.filter);
+        let bounds = self.tile_film_bounds(tile);
+        let mut film = Film::section(
+            columns,
+            rows,
+            bounds.x,
+            bounds.y,
+            bounds.x + bounds.width,
+            bounds.y + bounds.height,
+        );
+        for y in tile.y..(tile.y + tile.height) {
+            if self.is_cancelled() {
+                break;
+            }
+            for x in tile.x..(tile.x + tile.width) {
+                seed_random(pixel_seed(
+                    self.seed,
+                    pass,
+                    y as u64 * columns as u64 + x as u64,
+                ));
+                for _ in 0..num_samples {
+                    let (sx, sy) = (x as f64 + rand(), y as f64 + rand());
+                    let ray = self
+                        .camera
+                        .make_ray(sx / columns as f64, 1.0 - sy / rows as f64);
+                    let sample = match ray {
+                        Some(ray) => {
+                            count_camera_ray();
+                            color(self.world, ray, 0)
+                        }
+                        None => Color::black(),
+                    };
+                    film.add_sample(sx, sy, sample, filter);
+                }
+            }
+        }
+        flush_stats();
+        film
+    }
+
+    /// The pixels that can be reached by samples taken within the tile.
+    pub(crate) fn tile_film_bounds(&self, tile: &Tile) -> Tile {
+        let reach = (self.filter.radius() + 0.5).ceil() as u32;
+        let (x, y) = (tile.x.saturating_sub(reach), tile.y.saturating_sub(reach));
+        let right = u32::min(self.columns, tile.x + tile.width + reach);
+        let bottom = u32::min(self.rows, tile.y + tile.height + reach);
+        Tile {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+
+    /// The width and height of the image in pixels.
+    pub(crate) fn size(&self) -> (u32, u32) {
+        (self.columns, self.rows)
+    }
+
+    /// Keeps rendering passes of pass_samples per pixel until the deadline or cancellation,
+    /// calling after_pass once each pass has been accumulated.
+    /// A pass is only started if the previous one suggests it will finish in time,
+    /// though at least one pass is always rendered. Returns the number of passes rendered.
+    pub fn render_until<F>(&self, deadline: Instant, pass_samples: u32, mut after_pass: F) -> u32
+    where
+        F: FnMut(&Self),
+    {
+        let mut passes = 0;
+        let mut pass_duration = Duration::from_secs(0);
+        loop {
+            let start = Instant::now();
+            if passes > 0 && start + pass_duration > deadline {
+                return passes;
+            }
+            if !self.render_pass(pass_samples) {
+                return passes;
+            }
+            pass_duration = start.elapsed();
+            passes += 1;
+            after_pass(self);
+        }
+    }
+
+    /// The number of passes rendered so far.
+    pub fn passes(&self) -> u32 {
+        self.accumulation.lock().unwrap().passes
+    }
+
+    /// The number of samples taken per pixel so far.
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.accumulation.lock().unwrap().samples_per_pixel
+    }
+
+    /// Captures the render so far so it can be saved and resumed.
+    pub fn checkpoint(&self) -> io::Result<Checkpoint> {
+        let accumulation = self.accumulation.lock().unwrap();
+        // round trip the film rather than requiring it to be Clone
+        let mut bytes = vec![];
+        accumulation.film.write_to(&mut bytes)?;
+        Ok(Checkpoint {
+            seed: self.seed,
+            passes: accumulation.passes,
+            samples_per_pixel: accumulation.samples_per_pixel,
+            filter: format!("{:?}", self.filter),
+            region: self.region,
+            film: Film::read_from(&mut bytes.as_slice())?,
+        })
+    }
+
+    /// Continues from a checkpoint, replacing anything rendered so far.
+    /// The checkpoint must have been taken from a render of the same size, filter and region.
+    pub fn resume(&mut self, checkpoint: Checkpoint) -> io::Result<()> {
+        let film = &checkpoint.film;
+        let filter = format!("{:?}", self.filter);
+        let mismatch = if film.columns() != self.columns || film.rows() != self.rows {
+            Some(format!(
+                "Checkpoint is {}x{} but the render is {}x{}",
+                film.columns(),
+                film.rows(),
+                self.columns,
+                self.rows
+            ))
+        } else if !film.is_whole() {
+            Some("Checkpoint film does not cover the whole image".to_string())
+        } else if checkpoint.filter != filter {
+            Some(format!(
+                "Checkpoint was rendered with {} but the render uses {}",
+                checkpoint.filter, filter
+            ))
+        } else if checkpoint.region != self.region {
+            Some(format!(
+                "Checkpoint covers region {:?} but the render covers {:?}",
+                checkpoint.region, self.region
+            ))
+        } else {
+            None
+        };
+        if let Some(message) = mismatch {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+
+        self.seed = checkpoint.seed;
+        self.accumulation = Mutex::new(Accumulation {
+            film: checkpoint.film,
+            passes: checkpoint.passes,
+            samples_per_pixel: checkpoint.samples_per_pixel,
+            interrupted: None,
+        });
+        Ok(())
+    }
+
+    /// Returns the linear colors accumulated so far with the exposure applied, row by row.
+    pub fn linear_image(&self) -> Vec<Color> {
+        let accumulation = self.accumulation.lock().unwrap();
+        let colors = match &accumulation.interrupted {
+            Some(interrupted) => {
+                let mut film = Film::new(self.columns, self.rows);
+                film.merge(&accumulation.film);
+                film.merge(interrupted);
+                film.resolve()
+            }
+            None => accumulation.film.resolve(),
+        };
+        let columns = self.columns as usize;
+        colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| {
+                let (x, y) = ((i % columns) as u32, (i / columns) as u32);
+                if self.region.contains(x, y) {
+                    color * self.exposure
+                } else {
+                    Color::black()
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the gamma-adjusted colors accumulated so far, row by row.
+    pub fn image(&self) -> Vec<Color> {
+        self.linear_image()
+            .iter()
+            .map(|linear| linear.map(f64::sqrt)) // gamma adjust
+            .collect()
+    }
+}
+
+/// Derives the random seed for one pixel of one pass from the render seed.
+/// Every pixel of a pass gets its own seed, even in images of more than 2^32 pixels.
+fn pixel_seed(seed: u64, pass: u32, pixel: u64) -> u64 {
+    // splitmix64 finalizer, so neighboring pixels get unrelated streams
+    let mut z = seed ^ ((pass as u64) << 32) ^ pixel;
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        BoxFilter, Color, Lambertian, Normal3, PerspectiveCamera, Plane, Point3, PointLight,
+        Sphere, TentFilter, Vec3,
+    };
+    use super::*;
+
+    fn scene() -> (World, PerspectiveCamera) {
+        let mut world = World::new();
+        world.push(Box::new(Sphere {
+            center: Point3::new(0.0, 0.0, -1.0),
+            radius: 0.5,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        }));
+        let camera = PerspectiveCamera::new(
+            Vec3::fill(0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        (world, camera)
+    }
+
+    #[test]
+    fn pixels_past_four_billion_get_their_own_seeds() {
+        let far = 1 << 32;
+        assert_ne!(pixel_seed(3, 0, 5), pixel_seed(3, 0, far + 5));
+        assert_ne!(pixel_seed(3, 1, far), pixel_seed(3, 1, 0));
+    }
+
+    #[test]
+    fn passes_accumulate() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let renderer = ProgressiveRenderer::new(&world, &camera, 4, 6, &filter);
+        assert_eq!(renderer.image(), vec![Color::black(); 24]);
+
+        renderer.render_pass(2);
+        renderer.render_pass(3);
+        assert_eq!(renderer.passes(), 2);
+        assert_eq!(renderer.samples_per_pixel(), 5);
+
+        let image = renderer.image();
+        assert_eq!(image.len(), 24);
+        // corners see the sky, the center sees the sphere
+        assert!(image[0].b() > 0.9);
+        assert!(image[2 * 6 + 3].b() < image[0].b());
+    }
+
+    #[test]
+    fn lights_cast_shadows() {
+        let mut world = World::new();
+        world.push(Box::new(Plane {
+            point: Point3::origin(),
+            normal: Normal3::new(0.0, 1.0, 0.0),
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        }));
+        world.add_light(Box::new(PointLight {
+            position: Point3::new(0.0, 2.0, 0.0),
+            intensity: Color::fill(4.0),
+        }));
+        let down = |x| Ray::new(Vec3::new(x, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let light_at = |world: &World, x| {
+            let ray = down(x);
+            let hit = world.hit(&ray, 0.001, f64::MAX).unwrap();
+            direct_light(world, &ray, &hit)
+        };
+        // straight below, the light arrives head on from 2 away
+        let below = light_at(&world, 0.0);
+        assert!((below.r() - 0.5 / std::f64::consts::PI).abs() < 1e-12);
+        // 2 to the side the squared distance doubles and the light arrives at 45 degrees
+        let slanted = below.r() * (4.0 / 8.0) * std::f64::consts::FRAC_1_SQRT_2;
+        assert!((light_at(&world, 2.0).r() - slanted).abs() < 1e-12);
+
+        world.push(Box::new(Sphere {
+            center: Point3::new(0.0, 1.0, 0.0),
+            radius: 0.25,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(0.5),
+            }),
+        }));
+        let ray = Ray::new(Vec3::new(0.5, 1.0, 0.0), Vec3::new(-0.5, -1.0, 0.0));
+        let hit = world.hit(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.p, Point3::origin());
+        assert_eq!(direct_light(&world, &ray, &hit), Color::black());
+    }
+
+    #[test]
+    fn exposure_scales_the_image() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 4, 6, &filter);
+        renderer.render_pass(2);
+        let before = renderer.linear_image();
+        renderer.set_exposure(0.25);
+        let after = renderer.linear_image();
+        assert_eq!(after[0], before[0] * 0.25);
+        assert_eq!(renderer.image()[0], (before[0] * 0.25).map(f64::sqrt));
+    }
+
+    #[test]
+    fn render_until_deadline() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let renderer = ProgressiveRenderer::new(&world, &camera, 4, 6, &filter);
+
+        // a deadline in the past still renders one pass
+        assert_eq!(renderer.render_until(Instant::now(), 2, |_| {}), 1);
+        assert_eq!(renderer.samples_per_pixel(), 2);
+
+        let mut reported = 0;
+        let passes = renderer.render_until(Instant::now() + Duration::from_millis(50), 1, |r| {
+            reported = r.passes();
+        });
+        assert!(passes >= 1);
+        assert_eq!(renderer.passes(), passes + 1);
+        assert_eq!(reported, renderer.passes());
+    }
+
+    #[test]
+    fn tiles_report_progress() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 10, 12, &filter);
+        renderer.set_seed(3);
+        renderer.set_tiles(4, TileOrder::Hilbert);
+
+        let finished = Mutex::new(vec![]);
+        renderer.render_pass_with_progress(2, |tile, _| finished.lock().unwrap().push(*tile));
+        let finished = finished.into_inner().unwrap();
+        assert_eq!(finished.len(), 3 * 3);
+        assert_eq!(finished.iter().map(Tile::area).sum::<u32>(), 10 * 12);
+
+        // tiling doesn't change which samples are taken
+        let mut scanline = ProgressiveRenderer::new(&world, &camera, 10, 12, &filter);
+        scanline.set_seed(3);
+        scanline.set_tiles(5, TileOrder::Scanline);
+        scanline.render_pass(2);
+        assert_eq!(scanline.linear_image(), renderer.linear_image());
+    }
+
+    #[test]
+    fn region_matches_full_render() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let mut full = ProgressiveRenderer::new(&world, &camera, 8, 10, &filter);
+        full.set_seed(5);
+        full.render_pass(2);
+
+        let mut cropped = ProgressiveRenderer::new(&world, &camera, 8, 10, &filter);
+        cropped.set_seed(5);
+        let region = Tile {
+            x: 3,
+            y: 2,
+            width: 4,
+            height: 5,
+        };
+        cropped.set_region(region).unwrap();
+        cropped.render_pass(2);
+
+        let (full, cropped) = (full.linear_image(), cropped.linear_image());
+        for y in 0..8 {
+            for x in 0..10 {
+                let i = (y * 10 + x) as usize;
+                if region.contains(x, y) {
+                    assert_eq!(cropped[i], full[i]);
+                } else {
+                    assert_eq!(cropped[i], Color::black());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn region_edges_gather_samples_from_outside() {
+        let (world, camera) = scene();
+        let filter = TentFilter { radius: 1.5 };
+        let mut full = ProgressiveRenderer::new(&world, &camera, 8, 10, &filter);
+        full.set_seed(5);
+        full.render_pass(2);
+
+        let mut cropped = ProgressiveRenderer::new(&world, &camera, 8, 10, &filter);
+        cropped.set_seed(5);
+        let region = Tile {
+            x: 3,
+            y: 2,
+            width: 4,
+            height: 5,
+        };
+        cropped.set_region(region).unwrap();
+        assert_eq!(cropped.tiles().iter().map(Tile::area).sum::<u32>(), 6 * 7);
+        cropped.render_pass(2);
+
+        let (full, cropped) = (full.linear_image(), cropped.linear_image());
+        for y in 0..8 {
+            for x in 0..10 {
+                let i = (y * 10 + x) as usize;
+                if region.contains(x, y) {
+                    // tiles are merged in a different order, so sums may differ in the last bits
+                    assert!((cropped[i] - full[i])
+                        .channels()
+                        .iter()
+                        .all(|c| c.abs() < 1e-12));
+                } else {
+                    assert_eq!(cropped[i], Color::black());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn regions_are_clipped_to_the_image() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 8, 10, &filter);
+        let region = Tile {
+            x: 6,
+            y: 6,
+            width: 10,
+            height: 10,
+        };
+        renderer.set_region(region).unwrap();
+        assert_eq!(
+            renderer.region(),
+            Tile {
+                x: 6,
+                y: 6,
+                width: 4,
+                height: 2
+            }
+        );
+        assert!(renderer.set_region(Tile { x: 10, ..region }).is_err());
+    }
+
+    #[test]
+    fn cancel_keeps_partial_image() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 8, 8, &filter);
+        let token = CancellationToken::new();
+        renderer.set_cancellation_token(token.clone());
+        renderer.set_tiles(4, TileOrder::Scanline);
+        assert!(renderer.render_pass(1));
+
+        // cancel once the first tile of the second pass is done
+        let completed = renderer.render_pass_with_progress(1, |_, _| token.cancel());
+        assert!(!completed);
+        assert!(renderer.is_cancelled());
+        assert_eq!(renderer.passes(), 1);
+        assert_eq!(renderer.checkpoint().unwrap().passes, 1);
+        assert_eq!(renderer.linear_image().len(), 64);
+
+        assert!(!renderer.render_pass(1));
+        assert_eq!(
+            renderer.render_until(Instant::now() + Duration::from_secs(60), 1, |_| {}),
+            0
+        );
+    }
+
+    #[test]
+    fn cancel_before_rendering() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+        let mut renderer = ProgressiveRenderer::new(&world, &camera, 8, 8, &filter);
+        let token = CancellationToken::new();
+        renderer.set_cancellation_token(token.clone());
+        token.cancel();
+        assert!(!renderer.render_pass(4));
+        assert_eq!(renderer.image(), vec![Color::black(); 64]);
+    }
+
+    #[test]
+    fn resume_matches_uninterrupted_render() {
+        let (world, camera) = scene();
+        let filter = BoxFilter { radius: 0.5 };
+
+        let mut uninterrupted = ProgressiveRenderer::new(&world, &camera, 4, 6, &filter);
+        uninterrupted.set_seed(42);
+        uninterrupted.render_pass(2);
+        uninterrupted.render_pass(3);
+
+        let mut interrupted = ProgressiveRenderer::new(&world, &camera, 4, 6, &filter);
+        interrupted.set_seed(42);
+        interrupted.render_pass(2);
+        let mut bytes = vec![];
+        interrupted
+            .checkpoint()
+            .unwrap()
+            .write_to(&mut bytes)
+            .unwrap();
+
+        let mut resumed = ProgressiveRenderer::new(&world, &camera, 4, 6, &filter);
+        resumed
+            .resume(Checkpoint::read_from(&mut bytes.as_slice()).unwrap())
+            .unwrap();
+        assert_eq!(resumed.seed(), 42);
+        resumed.render_pass(3);
+
+        assert_eq!(resumed.samples_per_pixel(), 5);
+        assert_eq!(resumed.linear_image(), uninterrupted.linear_image());
+
+        let mut other_size = ProgressiveRenderer::new(&world, &camera, 6, 4, &filter);
+        assert!(other_size.resume(resumed.checkpoint().unwrap()).is_err());
+
+        let tent = TentFilter { radius: 1.0 };
+        let mut other_filter = ProgressiveRenderer::new(&world, &camera, 4, 6, &tent);
+        assert!(other_filter.resume(resumed.checkpoint().unwrap()).is_err());
+
+        let mut other_region = ProgressiveRenderer::new(&world, &camera, 4, 6, &filter);
+        other_region
+            .set_region(Tile {
+                x: 1,
+                y: 1,
+                width: 2,
+                height: 2,
+            })
+            .unwrap();
+        assert!(other_region.resume(resumed.checkpoint().unwrap()).is_err());
+    }
+}
@@ -1,45 +1,76 @@
 use super::HitRecord;
-use super::{dot, normalize, rand, random_in_unit_sphere, reflect, refract, schlick, Ray, Vec3};
+use super::{
+    dot, normalize, rand, random_in_unit_sphere, reflect, refract, schlick, Color, Ray, Vec3,
+};
+use std::f64::consts::PI;
 
 /// A Ray after scattering off a Hitable
 pub struct ScatteredRay {
     pub ray: Ray,
-    pub attenuation: Vec3,
+    pub attenuation: Color,
 }
 
 /// Scattering determines how a ray behaves after hitting a Hitable
-pub trait Scattering {
+/// Materials are shared between render threads, and with objects placed more than once.
+pub trait Scattering: Send + Sync {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay>;
+
+    /// How much of the light arriving from direction (a unit vector) leaves back along
+    /// the ray, including the cosine of the angle it arrives at. Lights are sampled with this.
+    /// Materials that only reflect in exact directions never catch light from a point,
+    /// so by default nothing is reflected.
+    fn reflectance(&self, _ray: &Ray, _hit: &HitRecord, _direction: Vec3) -> Color {
+        Color::black()
+    }
 }
 
 /// Lambertian scattering is perfectly diffuse
 pub struct Lambertian {
-    pub albedo: Vec3,
+    pub albedo: Color,
 }
 
 impl Scattering for Lambertian {
-    fn scatter(&self, _: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
         let target = hit.p + hit.normal + random_in_unit_sphere();
-        let ray = Ray::new(hit.p, target - hit.p);
+        let scattered = Ray::at_time(hit.p, target - hit.p, ray.time());
         Some(ScatteredRay {
-            ray,
+            ray: scattered,
             attenuation: self.albedo,
         })
     }
+
+    fn reflectance(&self, ray: &Ray, hit: &HitRecord, direction: Vec3) -> Color {
+        // light only reaches the side of the surface the ray came from
+        let facing = if dot(ray.direction(), hit.normal) > 0.0 {
+            -*hit.normal()
+        } else {
+            *hit.normal()
+        };
+        let cosine = dot(facing, direction);
+        if cosine > 0.0 {
+            self.albedo * (cosine / PI)
+        } else {
+            Color::black()
+        }
+    }
 }
 
 /// Metallic scattering reflects rays at a consistent angle
 pub struct Metallic {
-    pub albedo: Vec3,
+    pub albedo: Color,
     pub roughness: f64,
 }
 
 impl Scattering for Metallic {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
-        let reflected = reflect(&normalize(ray.direction()), &hit.normal);
-        let scattered = Ray::new(hit.p, reflected + random_in_unit_sphere() * self.roughness);
-
-        if dot(scattered.direction(), &hit.normal) > 0.0 {
+        let reflected = reflect(normalize(ray.direction()), hit.normal);
+        let scattered = Ray::at_time(
+            hit.p,
+            reflected + random_in_unit_sphere() * self.roughness,
+            ray.time(),
+        );
+
+        if dot(scattered.direction(), hit.normal) > 0.0 {
             return Some(ScatteredRay {
                 attenuation: self.albedo,
                 ray: scattered,
@@ -48,6 +79,33 @@ impl Scattering for Metallic {
 
         None
     }
+
+    /// Rough metal scatters towards points spread evenly over a sphere of radius roughness
+    /// around the mirror direction, so a light reflects with the density of those points
+    /// seen from the hit. Polished metal only reflects in the mirror direction.
+    fn reflectance(&self, ray: &Ray, hit: &HitRecord, direction: Vec3) -> Color {
+        let radius = self.roughness;
+        if radius <= 0.0 || dot(direction, hit.normal) <= 0.0 {
+            return Color::black();
+        }
+        // the direction meets the sphere where t^2 - 2bt + c = 0
+        let reflected = reflect(normalize(ray.direction()), hit.normal);
+        let b = dot(direction, reflected);
+        let c = reflected.length_squared() - radius * radius;
+        let discriminant = b * b - c;
+        if discriminant <= 0.0 {
+            return Color::black();
+        }
+        // a patch of the sphere t away covers t^2 / cos of solid angle, and the cosine
+        // between the direction and the sphere's surface is root / radius at both crossings
+        let root = discriminant.sqrt();
+        let area: f64 = [b - root, b + root]
+            .iter()
+            .filter(|&&t| t > 0.0)
+            .map(|t| t * t)
+            .sum();
+        self.albedo * (area / (4.0 * PI * radius * root))
+    }
 }
 
 /// Dielectric materials refract light, like glass.
@@ -57,7 +115,7 @@ pub struct Dielectric {
 
 impl Scattering for Dielectric {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
-        let attenuation = Vec3::new(1.0, 1.0, 1.0);
+        let attenuation = Color::fill(1.0);
         let outward_normal;
         let ni_over_nt;
         let cosine;
@@ -73,12 +131,12 @@ impl Scattering for Dielectric {
             cosine = -dot(ray.direction(), hit.normal()) / ray.direction().length();
         }
 
-        if let Some(refracted) = refract(ray.direction(), &outward_normal, ni_over_nt) {
+        if let Some(refracted) = refract(ray.direction(), outward_normal, ni_over_nt) {
             let reflect_prob = schlick(cosine, self.refractive_index);
             if rand() > reflect_prob {
                 // refract
                 return Some(ScatteredRay {
-                    ray: Ray::new(*hit.position(), refracted),
+                    ray: Ray::at_time(*hit.position(), refracted, ray.time()),
                     attenuation,
                 });
             }
@@ -86,7 +144,7 @@ impl Scattering for Dielectric {
 
         let reflected = reflect(ray.direction(), hit.normal());
         Some(ScatteredRay {
-            ray: Ray::new(*hit.position(), reflected),
+            ray: Ray::at_time(*hit.position(), reflected, ray.time()),
             attenuation,
         })
     }
@@ -98,31 +156,91 @@ pub struct NaiveDielectric {
 
 impl Scattering for NaiveDielectric {
     fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<ScatteredRay> {
-        let attenuation = Vec3::new(1.0, 1.0, 1.0);
+        let attenuation = Color::fill(1.0);
 
         let outward_normal: Vec3;
         let ni_over_nt: f64;
         if dot(ray.direction(), hit.normal()) > 0.0 {
-            outward_normal = &Vec3::new(0.0, 0.0, 0.0) - hit.normal();
+            outward_normal = Vec3::new(0.0, 0.0, 0.0) - hit.normal();
             ni_over_nt = self.refractive_index;
         } else {
             outward_normal = *hit.normal();
             ni_over_nt = 1.0 / self.refractive_index;
         }
 
-        if let Some(refracted) = refract(ray.direction(), &outward_normal, ni_over_nt) {
-            let scattered = Ray::new(*hit.position(), refracted);
-            return Some(ScatteredRay {
+        if let Some(refracted) = refract(ray.direction(), outward_normal, ni_over_nt) {
+            let scattered = Ray::at_time(*hit.position(), refracted, ray.time());
+            Some(ScatteredRay {
                 ray: scattered,
                 attenuation,
-            });
+            })
         } else {
             let reflected = reflect(ray.direction(), hit.normal());
-            let scattered = Ray::new(*hit.position(), reflected);
-            return Some(ScatteredRay {
+            let scattered = Ray::at_time(*hit.position(), reflected, ray.time());
+            Some(ScatteredRay {
                 ray: scattered,
                 attenuation,
-            });
+            })
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::{seed_random, Normal3, Point3};
+    use super::*;
+
+    #[test]
+    fn rough_metal_reflects_lights_like_its_scattered_rays() {
+        let metal = Metallic {
+            albedo: Color::fill(1.0),
+            roughness: 0.5,
+        };
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let hit = HitRecord {
+            t: 1.0,
+            p: Point3::origin(),
+            normal: Normal3::new(0.0, 1.0, 0.0),
+            u: 0.0,
+            v: 0.0,
+            material: &metal,
+        };
+        // how much light arrives from within an angle of the mirror direction
+        let within = |limit: f64| {
+            let steps = 100_000;
+            let step = limit / steps as f64;
+            (0..steps)
+                .map(|i| {
+                    let theta = (i as f64 + 0.5) * step;
+                    let direction = Vec3::new(theta.sin(), theta.cos(), 0.0);
+                    let density = metal.reflectance(&ray, &hit, direction).channels()[0];
+                    density * 2.0 * PI * theta.sin() * step
+                })
+                .sum::<f64>()
+        };
+        // every scattered ray stays within 30 degrees, and none are absorbed
+        let cone = 0.5f64.asin();
+        assert!((within(cone) - 1.0).abs() < 1e-2);
+        assert_eq!(
+            metal.reflectance(&ray, &hit, normalize(Vec3::new(1.0, 1.0, 0.0))),
+            Color::black()
+        );
+
+        seed_random(7);
+        let samples = 100_000;
+        let near = (0..samples)
+            .filter(|_| {
+                let scattered = metal.scatter(&ray, &hit).unwrap();
+                normalize(scattered.ray.direction()).y() > 10f64.to_radians().cos()
+            })
+            .count();
+        assert!((near as f64 / samples as f64 - within(10f64.to_radians())).abs() < 1e-2);
+
+        let polished = Metallic {
+            roughness: 0.0,
+            ..metal
+        };
+        let up = Vec3::new(0.0, 1.0, 0.0);
+        assert_eq!(polished.reflectance(&ray, &hit, up), Color::black());
+    }
+}
@@ -1,64 +1,152 @@
-use super::{dot, Ray, Vec3};
+use super::stats::count_intersection_test;
+use super::{dot, Aabb, Normal3, Point3, Ray, Vec3};
 use super::{HitRecord, Hitable, Scattering};
+use std::f64::consts::{FRAC_PI_2, PI};
 
 /// A Sphere at a given position
 pub struct Sphere {
-    pub center: Vec3,
+    pub center: Point3,
     pub radius: f64,
     pub material: Box<dyn Scattering + Sync>, // for testability, would be easier not to complect with materials
 }
 
 impl Hitable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let oc = ray.origin() - &self.center;
-        let a = dot(ray.direction(), ray.direction());
-        let b = dot(oc, ray.direction());
-        let c = dot(oc, oc) - self.radius * self.radius;
-        let discriminant = b * b - a * c;
-        if discriminant > 0.0 {
-            let t = (-b - f64::sqrt(discriminant)) / a;
-            if t < t_max && t > t_min {
-                let p = ray.point_at_parameter(t);
-                return Some(HitRecord {
-                    t,
-                    p,
-                    normal: (&p - &self.center) / self.radius,
-                    material: &*self.material,
-                });
-            }
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        hit_sphere(self.center, self.radius, &*self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _: f64, _: f64) -> Option<Aabb> {
+        Some(sphere_box(self.center, self.radius))
+    }
+}
+
+/// A sphere moving in a straight line, centered at center0 at time0 and at center1 at time1.
+pub struct MovingSphere {
+    pub center0: Point3,
+    pub center1: Point3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Box<dyn Scattering + Sync>,
+}
+
+impl MovingSphere {
+    /// Where the center is at the given time. It keeps moving at the same speed
+    /// outside [time0, time1].
+    pub fn center(&self, time: f64) -> Point3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let t = (time - self.time0) / (self.time1 - self.time0);
+        self.center0 + (self.center1 - self.center0) * t
+    }
+}
+
+impl Hitable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
+        let center = self.center(ray.time());
+        hit_sphere(center, self.radius, &*self.material, ray, t_min, t_max)
+    }
 
-            let t = (-b + f64::sqrt(discriminant)) / a;
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        // the motion is linear, so the ends of the interval bound everything between
+        let start = sphere_box(self.center(time0), self.radius);
+        Some(start.union(&sphere_box(self.center(time1), self.radius)))
+    }
+}
+
+fn sphere_box(center: Point3, radius: f64) -> Aabb {
+    let extent = Vec3::fill(radius.abs());
+    Aabb::new(center - extent, center + extent)
+}
+
+fn hit_sphere<'a>(
+    center: Point3,
+    radius: f64,
+    material: &'a dyn Scattering,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<HitRecord<'a>> {
+    count_intersection_test();
+    let oc = *ray.origin() - center;
+    let a = dot(ray.direction(), ray.direction());
+    let b = dot(oc, ray.direction());
+    let c = dot(oc, oc) - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant > 0.0 {
+        for &t in &[
+            (-b - f64::sqrt(discriminant)) / a,
+            (-b + f64::sqrt(discriminant)) / a,
+        ] {
             if t < t_max && t > t_min {
                 let p = ray.point_at_parameter(t);
+                let normal = (p - center) / radius;
+                let (u, v) = sphere_uv(&normal);
                 return Some(HitRecord {
                     t,
                     p,
-                    normal: (&p - &self.center) / self.radius,
-                    material: &*self.material,
+                    normal: Normal3(normal),
+                    u,
+                    v,
+                    material,
                 });
             }
         }
-        None
     }
+    None
+}
+
+/// Longitude and latitude of a point on the unit sphere, scaled to [0, 1].
+/// u runs around the y axis starting from -x, v from the bottom pole to the top.
+fn sphere_uv(p: &Vec3) -> (f64, f64) {
+    let phi = f64::atan2(p.z(), p.x());
+    let theta = f64::asin(p.y().clamp(-1.0, 1.0));
+    (1.0 - (phi + PI) / (2.0 * PI), (theta + FRAC_PI_2) / PI)
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::Color;
     use super::super::Lambertian;
-    use super::{Hitable, Ray, Sphere, Vec3};
+    use super::*;
 
     #[test]
     fn sphere_at_origin() {
         let sphere = Sphere {
-            center: Vec3::fill(0.0),
+            center: Point3::origin(),
             radius: 1.0,
             material: Box::new(Lambertian {
-                albedo: Vec3::fill(1.0),
+                albedo: Color::fill(1.0),
             }),
         };
 
         let ray = Ray::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, 1.0));
         let hit = sphere.hit(&ray, 0.0, std::f64::MAX).unwrap();
-        assert_eq!(hit.p, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(hit.p, Point3::new(0.0, 0.0, -1.0));
+        assert_eq!((hit.u, hit.v), (0.75, 0.5));
+    }
+
+    #[test]
+    fn moving_sphere_follows_time() {
+        let sphere = MovingSphere {
+            center0: Point3::new(0.0, 0.0, 0.0),
+            center1: Point3::new(0.0, 2.0, 0.0),
+            time0: 0.0,
+            time1: 1.0,
+            radius: 0.5,
+            material: Box::new(Lambertian {
+                albedo: Color::fill(1.0),
+            }),
+        };
+        assert_eq!(sphere.center(0.5), Point3::new(0.0, 1.0, 0.0));
+        let at = |time| Ray::at_time(Vec3::new(0.0, 2.0, -2.0), Vec3::new(0.0, 0.0, 1.0), time);
+        assert!(sphere.hit(&at(0.0), 0.0, f64::MAX).is_none());
+        let hit = sphere.hit(&at(1.0), 0.0, f64::MAX).unwrap();
+        assert_eq!(hit.p, Point3::new(0.0, 2.0, -0.5));
+
+        let bounds = sphere.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!(bounds.min, Point3::new(-0.5, -0.5, -0.5));
+        assert_eq!(bounds.max, Point3::new(0.5, 2.5, 0.5));
     }
 }
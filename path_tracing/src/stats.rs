@@ -0,0 +1,202 @@
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+// Counting happens in thread-local cells so tracing rays never contends on shared memory;
+// the renderer flushes them into the process-wide totals after every tile.
+thread_local! {
+    static CAMERA_RAYS: Cell<u64> = const { Cell::new(0) };
+    static BOUNCE_RAYS: Cell<u64> = const { Cell::new(0) };
+    static SHADOW_RAYS: Cell<u64> = const { Cell::new(0) };
+    static INTERSECTION_TESTS: Cell<u64> = const { Cell::new(0) };
+}
+
+static TOTAL_CAMERA_RAYS: AtomicU64 = AtomicU64::new(0);
+static TOTAL_BOUNCE_RAYS: AtomicU64 = AtomicU64::new(0);
+static TOTAL_SHADOW_RAYS: AtomicU64 = AtomicU64::new(0);
+static TOTAL_INTERSECTION_TESTS: AtomicU64 = AtomicU64::new(0);
+
+fn increment(counter: &'static std::thread::LocalKey<Cell<u64>>, amount: u64) {
+    counter.with(|count| count.set(count.get() + amount));
+}
+
+pub(crate) fn count_camera_ray() {
+    increment(&CAMERA_RAYS, 1);
+}
+
+pub(crate) fn count_bounce_ray() {
+    increment(&BOUNCE_RAYS, 1);
+}
+
+pub(crate) fn count_shadow_ray() {
+    increment(&SHADOW_RAYS, 1);
+}
+
+/// Counts a ray tested against a sphere, rectangle or plane. Objects made of these,
+/// or placed by a transform, count the tests they pass on.
+pub(crate) fn count_intersection_test() {
+    increment(&INTERSECTION_TESTS, 1);
+}
+
+/// The intersection tests counted on this thread since its counts were last flushed.
+#[cfg(test)]
+pub(crate) fn unflushed_intersection_tests() -> u64 {
+    INTERSECTION_TESTS.with(Cell::get)
+}
+
+/// Adds the counts gathered on this thread to the process-wide totals.
+pub(crate) fn flush_stats() {
+    for (local, total) in &[
+        (&CAMERA_RAYS, &TOTAL_CAMERA_RAYS),
+        (&BOUNCE_RAYS, &TOTAL_BOUNCE_RAYS),
+        (&SHADOW_RAYS, &TOTAL_SHADOW_RAYS),
+        (&INTERSECTION_TESTS, &TOTAL_INTERSECTION_TESTS),
+    ] {
+        total.fetch_add(local.with(|count| count.replace(0)), Ordering::Relaxed);
+    }
+}
+
+/// Ray counts gathered by all the renders in this process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    pub bounce_rays: u64,
+    /// Rays towards lights, checking whether anything is in the way.
+    pub shadow_rays: u64,
+    /// Rays tested against the spheres, rectangles and planes the objects are made of.
+    pub intersection_tests: u64,
+}
+
+impl RenderStats {
+    /// Returns the counts gathered since the process started or the last reset.
+    pub fn collect() -> RenderStats {
+        RenderStats {
+            camera_rays: TOTAL_CAMERA_RAYS.load(Ordering::Relaxed),
+            bounce_rays: TOTAL_BOUNCE_RAYS.load(Ordering::Relaxed),
+            shadow_rays: TOTAL_SHADOW_RAYS.load(Ordering::Relaxed),
+            intersection_tests: TOTAL_INTERSECTION_TESTS.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn reset() {
+        TOTAL_CAMERA_RAYS.store(0, Ordering::Relaxed);
+        TOTAL_BOUNCE_RAYS.store(0, Ordering::Relaxed);
+        TOTAL_SHADOW_RAYS.store(0, Ordering::Relaxed);
+        TOTAL_INTERSECTION_TESTS.store(0, Ordering::Relaxed);
+    }
+
+    pub fn total_rays(&self) -> u64 {
+        self.camera_rays + self.bounce_rays + self.shadow_rays
+    }
+
+    /// The average number of bounces a path took before leaving the scene or being absorbed.
+    pub fn average_path_depth(&self) -> f64 {
+        ratio(self.bounce_rays, self.camera_rays)
+    }
+
+    pub fn intersection_tests_per_ray(&self) -> f64 {
+        ratio(self.intersection_tests, self.total_rays())
+    }
+
+    pub fn rays_per_second(&self, render_time: Duration) -> f64 {
+        let seconds = render_time.as_secs_f64();
+        if seconds > 0.0 {
+            self.total_rays() as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Formats the counts, derived rates and the time spent in each named phase as JSON.
+    /// Rays per second are measured against the phase named "render", if any.
+    pub fn to_json(&self, phases: &[(&str, Duration)]) -> String {
+        let render_time = phases
+            .iter()
+            .find(|(name, _)| *name == "render")
+            .map_or(Duration::from_secs(0), |(_, time)| *time);
+        let phases: Vec<String> = phases
+            .iter()
+            .map(|(name, time)| format!("\"{}\": {}", name, time.as_secs_f64()))
+            .collect();
+        format!(
+            "{{\n  \"camera_rays\": {},\n  \"bounce_rays\": {},\n  \"shadow_rays\": {},\n  \
+             \"total_rays\": {},\n  \
+             \"rays_per_second\": {:.1},\n  \"average_path_depth\": {:.4},\n  \
+             \"intersection_tests\": {},\n  \"intersection_tests_per_ray\": {:.4},\n  \
+             \"phase_seconds\": {{{}}}\n}}\n",
+            self.camera_rays,
+            self.bounce_rays,
+            self.shadow_rays,
+            self.total_rays(),
+            self.rays_per_second(render_time),
+            self.average_path_depth(),
+            self.intersection_tests,
+            self.intersection_tests_per_ray(),
+            phases.join(", ")
+        )
+    }
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator > 0 {
+        numerator as f64 / denominator as f64
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derived_rates() {
+        let stats = RenderStats {
+            camera_rays: 100,
+            bounce_rays: 150,
+            shadow_rays: 0,
+            intersection_tests: 1000,
+        };
+        assert_eq!(stats.total_rays(), 250);
+        let lit = RenderStats {
+            shadow_rays: 50,
+            ..stats
+        };
+        assert_eq!(lit.total_rays(), 300);
+        assert_eq!(stats.average_path_depth(), 1.5);
+        assert_eq!(stats.intersection_tests_per_ray(), 4.0);
+        assert_eq!(stats.rays_per_second(Duration::from_millis(500)), 500.0);
+        assert_eq!(RenderStats::default().average_path_depth(), 0.0);
+    }
+
+    #[test]
+    fn json_report() {
+        let stats = RenderStats {
+            camera_rays: 4,
+            bounce_rays: 2,
+            shadow_rays: 0,
+            intersection_tests: 12,
+        };
+        let json = stats.to_json(&[
+            ("scene", Duration::from_millis(250)),
+            ("render", Duration::from_secs(2)),
+        ]);
+        assert!(json.contains("\"rays_per_second\": 3.0,"));
+        assert!(json.contains("\"phase_seconds\": {\"scene\": 0.25, \"render\": 2}"));
+    }
+
+    #[test]
+    fn counters_flush_into_totals() {
+        let before = RenderStats::collect();
+        count_camera_ray();
+        count_bounce_ray();
+        for _ in 0..3 {
+            count_intersection_test();
+        }
+        flush_stats();
+        let after = RenderStats::collect();
+        // other tests may be rendering at the same time
+        assert!(after.camera_rays > before.camera_rays);
+        assert!(after.intersection_tests >= before.intersection_tests + 3);
+    }
+}
@@ -0,0 +1,173 @@
+use super::{Camera, EquirectangularCamera, PerspectiveCamera, Ray};
+
+/// How the two eyes share one image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StereoLayout {
+    /// The left eye in the top half and the right eye in the bottom half.
+    TopBottom,
+    /// The left eye in the left half and the right eye in the right half.
+    SideBySide,
+}
+
+/// Renders a left and a right eye view into one image, laid out for VR headsets
+/// and 3D displays. Each eye gets half of the image, so the eye cameras should be
+/// built for that half's aspect ratio.
+#[derive(Debug, Clone)]
+pub struct StereoCamera<C: Camera> {
+    pub left: C,
+    pub right: C,
+    pub layout: StereoLayout,
+}
+
+impl StereoCamera<PerspectiveCamera> {
+    /// Eyes interocular apart either side of camera, converging at the convergence
+    /// distance: things that far away appear at the same place in both eyes, nearer
+    /// things in front of the screen and further ones behind it.
+    /// The eyes look in parallel with their images shifted to converge, rather than
+    /// turning inwards, which would give vertical parallax towards the corners.
+    pub fn perspective(
+        camera: &PerspectiveCamera,
+        interocular: f64,
+        convergence: f64,
+        layout: StereoLayout,
+    ) -> StereoCamera<PerspectiveCamera> {
+        let eye = |side: f64| {
+            let offset = camera.u * (side * interocular / 2.0);
+            // along the view direction, which a shifted image's center is not
+            let focus_dist = camera.focus_distance();
+            PerspectiveCamera {
+                origin: camera.origin + offset,
+                // keep the same point in view at the convergence distance
+                lower_left_corner: camera.lower_left_corner
+                    + offset * (1.0 - focus_dist / convergence),
+                ..camera.clone()
+            }
+        };
+        StereoCamera {
+            left: eye(-1.0),
+            right: eye(1.0),
+            layout,
+        }
+    }
+}
+
+impl StereoCamera<OdsCamera> {
+    /// Omni-directional stereo from a panoramic camera, with the eyes interocular apart.
+    pub fn omnidirectional(
+        camera: &EquirectangularCamera,
+        interocular: f64,
+        layout: StereoLayout,
+    ) -> StereoCamera<OdsCamera> {
+        let eye = |side: f64| OdsCamera {
+            panorama: camera.clone(),
+            eye_offset: side * interocular / 2.0,
+        };
+        StereoCamera {
+            left: eye(-1.0),
+            right: eye(1.0),
+            layout,
+        }
+    }
+}
+
+impl<C: Camera> Camera for StereoCamera<C> {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        match self.layout {
+            StereoLayout::SideBySide if u < 0.5 => self.left.make_ray(u * 2.0, v),
+            StereoLayout::SideBySide => self.right.make_ray(u * 2.0 - 1.0, v),
+            StereoLayout::TopBottom if v >= 0.5 => self.left.make_ray(u, v * 2.0 - 1.0),
+            StereoLayout::TopBottom => self.right.make_ray(u, v * 2.0),
+        }
+    }
+}
+
+/// One eye of an omni-directional stereo panorama. Rather than a single viewpoint,
+/// each direction is seen from the point on a circle the eyes turn around,
+/// so every direction the viewer looks in has the right parallax.
+#[derive(Debug, Clone)]
+pub struct OdsCamera {
+    pub panorama: EquirectangularCamera,
+    /// How far the eye is to the right of the center, negative for the left eye.
+    pub eye_offset: f64,
+}
+
+impl Camera for OdsCamera {
+    fn make_ray(&self, u: f64, v: f64) -> Option<Ray> {
+        let ray = self.panorama.make_ray(u, v)?;
+        // the eye is to the right of the horizontal direction being looked in
+        let longitude = (u - 0.5) * 2.0 * std::f64::consts::PI;
+        let camera = &self.panorama;
+        let right = camera.u * longitude.cos() + camera.w * longitude.sin();
+        Some(Ray::at_time(
+            *ray.origin() + right * self.eye_offset,
+            *ray.direction(),
+            ray.time(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{cross, dot, Point3, Vec3};
+    use super::*;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn perspective_eyes_converge() {
+        let camera = PerspectiveCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            90.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let stereo = StereoCamera::perspective(&camera, 0.2, 5.0, StereoLayout::SideBySide);
+        assert_eq!(stereo.left.origin, Point3::new(-0.1, 0.0, 0.0));
+        assert_eq!(stereo.right.origin, Point3::new(0.1, 0.0, 0.0));
+
+        // the same pixel in each half sees the same point at the convergence distance
+        let left = stereo.make_ray(0.3 / 2.0, 0.6).unwrap();
+        let right = stereo.make_ray(0.5 + 0.3 / 2.0, 0.6).unwrap();
+        let at_convergence = |ray: &Ray| ray.point_at_parameter(-5.0 / ray.direction().z());
+        assert_near(at_convergence(&left).0, at_convergence(&right).0);
+
+        // shifting the image moves its center off the view axis but not the focus distance
+        let shifted = camera.clone().with_shift(0.4, 0.0);
+        let stereo = StereoCamera::perspective(&shifted, 0.2, 5.0, StereoLayout::SideBySide);
+        let left = stereo.make_ray(0.3 / 2.0, 0.6).unwrap();
+        let right = stereo.make_ray(0.5 + 0.3 / 2.0, 0.6).unwrap();
+        assert_near(at_convergence(&left).0, at_convergence(&right).0);
+
+        let top_bottom = StereoCamera::perspective(&camera, 0.2, 5.0, StereoLayout::TopBottom);
+        let top = top_bottom.make_ray(0.5, 0.75).unwrap();
+        assert_eq!(*top.origin(), stereo.left.origin);
+        let bottom = top_bottom.make_ray(0.5, 0.25).unwrap();
+        assert_eq!(*bottom.origin(), stereo.right.origin);
+    }
+
+    #[test]
+    fn ods_eyes_circle_the_center() {
+        let panorama = EquirectangularCamera::new(
+            Point3::origin(),
+            Point3::new(0.0, 0.0, -1.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        let stereo = StereoCamera::omnidirectional(&panorama, 0.2, StereoLayout::TopBottom);
+        for &u in &[0.1, 0.35, 0.5, 0.8] {
+            for &(v, eye) in &[(0.8, -0.1), (0.3, 0.1)] {
+                let ray = stereo.make_ray(u, v).unwrap();
+                let offset = ray.origin().0;
+                assert!((offset.length() - 0.1).abs() < 1e-12);
+                // the eye is beside the direction it looks in, to the right for the right eye
+                assert!(dot(offset, *ray.direction()).abs() < 1e-12);
+                let right = cross(*ray.direction(), Vec3::new(0.0, 1.0, 0.0));
+                assert!(dot(offset, right) * eye > 0.0);
+            }
+        }
+    }
+}
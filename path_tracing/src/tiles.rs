@@ -0,0 +1,293 @@
+/// A rectangle of pixels rendered as one unit of work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Tile {
+    pub fn area(&self) -> u32 {
+        self.width * self.height
+    }
+
+    /// The column just past the right edge, saturating for tiles that reach past u32::MAX.
+    fn right(&self) -> u32 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// The row just past the bottom edge, saturating for tiles that reach past u32::MAX.
+    fn bottom(&self) -> u32 {
+        self.y.saturating_add(self.height)
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x < self.right() && y < self.bottom()
+    }
+
+    /// Returns the part of this tile that overlaps another, if any.
+    pub fn intersection(&self, other: &Tile) -> Option<Tile> {
+        let x = u32::max(self.x, other.x);
+        let y = u32::max(self.y, other.y);
+        let right = u32::min(self.right(), other.right());
+        let bottom = u32::min(self.bottom(), other.bottom());
+        if right > x && bottom > y {
+            Some(Tile {
+                x,
+                y,
+                width: right - x,
+                height: bottom - y,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// The order in which tiles are handed out to render threads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileOrder {
+    /// Left to right, top to bottom.
+    Scanline,
+    /// Outward from the center of the image, where the subject usually is.
+    Spiral,
+    /// Along a Hilbert curve, so consecutive tiles are mostly neighbors. They always are
+    /// when the tiles form a square grid a power of two wide, and elsewhere the curve
+    /// jumps over the cells it would have visited outside the image.
+    Hilbert,
+}
+
+/// Splits a region of an image into tiles of (at most) tile_size pixels square,
+/// in the given order.
+pub fn tiles(region: &Tile, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = u32::max(tile_size, 1);
+    let across = region.width.div_ceil(tile_size);
+    let down = region.height.div_ceil(tile_size);
+    let tile = |(i, j): (u32, u32)| {
+        let (x, y) = (i * tile_size, j * tile_size);
+        Tile {
+            x: region.x + x,
+            y: region.y + y,
+            width: u32::min(tile_size, region.width - x),
+            height: u32::min(tile_size, region.height - y),
+        }
+    };
+
+    let grid = (0..down).flat_map(|j| (0..across).map(move |i| (i, j)));
+    match order {
+        TileOrder::Scanline => grid.map(tile).collect(),
+        TileOrder::Spiral => spiral(across, down).into_iter().map(tile).collect(),
+        TileOrder::Hilbert => {
+            let n = u32::max(across, down).next_power_of_two();
+            let mut cells: Vec<(u32, u32)> = grid.collect();
+            cells.sort_by_key(|&(i, j)| hilbert_index(n, i, j));
+            cells.into_iter().map(tile).collect()
+        }
+    }
+}
+
+/// Walks a square spiral outward from the center cell, keeping cells inside the grid.
+/// Legs are clipped to the grid, so long thin grids don't walk the whole enclosing square.
+fn spiral(across: u32, down: u32) -> Vec<(u32, u32)> {
+    let total = (across * down) as usize;
+    let mut cells = Vec::with_capacity(total);
+    let (mut i, mut j) = (((across as i64) - 1) / 2, ((down as i64) - 1) / 2);
+    let (mut di, mut dj) = (1, 0);
+    let mut leg = 1;
+    while cells.len() < total {
+        // each leg length is walked twice, turning after each
+        for _ in 0..2 {
+            let (first_i, end_i) = steps_inside(i, di, across as i64);
+            let (first_j, end_j) = steps_inside(j, dj, down as i64);
+            let first = first_i.max(first_j).max(0);
+            let end = end_i.min(end_j).min(leg);
+            for k in first..end {
+                cells.push(((i + k * di) as u32, (j + k * dj) as u32));
+            }
+            i += leg * di;
+            j += leg * dj;
+            let turned = (-dj, di);
+            di = turned.0;
+            dj = turned.1;
+        }
+        leg += 1;
+    }
+    cells
+}
+
+/// The steps k for which start + k * step lies in 0..size, as a half-open range.
+fn steps_inside(start: i64, step: i64, size: i64) -> (i64, i64) {
+    match step {
+        1 => (-start, size - start),
+        -1 => (start - size + 1, start + 1),
+        _ if (0..size).contains(&start) => (i64::MIN, i64::MAX),
+        _ => (0, 0),
+    }
+}
+
+/// Distance along a Hilbert curve filling an n by n grid, where n is a power of two.
+fn hilbert_index(n: u32, x: u32, y: u32) -> u64 {
+    let (mut x, mut y) = (x, y);
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        d += s as u64 * s as u64 * ((3 * rx) ^ ry) as u64;
+        // rotate the quadrant so the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x &= s - 1;
+        y &= s - 1;
+        s /= 2;
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(columns: u32, rows: u32) -> Tile {
+        Tile {
+            x: 0,
+            y: 0,
+            width: columns,
+            height: rows,
+        }
+    }
+
+    fn covers_image(tiles: &[Tile], columns: u32, rows: u32) -> bool {
+        let mut covered = vec![0; (columns * rows) as usize];
+        for tile in tiles {
+            for y in tile.y..tile.y + tile.height {
+                for x in tile.x..tile.x + tile.width {
+                    covered[(y * columns + x) as usize] += 1;
+                }
+            }
+        }
+        covered.iter().all(|&count| count == 1)
+    }
+
+    #[test]
+    fn every_order_covers_the_image_once() {
+        for order in &[TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            let tiles = tiles(&image(70, 45), 16, *order);
+            assert_eq!(tiles.len(), 5 * 3);
+            assert!(covers_image(&tiles, 70, 45), "{:?} tiles", order);
+        }
+    }
+
+    #[test]
+    fn edge_tiles_are_clipped() {
+        let tiles = tiles(&image(20, 10), 16, TileOrder::Scanline);
+        assert_eq!(
+            tiles,
+            vec![
+                Tile {
+                    x: 0,
+                    y: 0,
+                    width: 16,
+                    height: 10
+                },
+                Tile {
+                    x: 16,
+                    y: 0,
+                    width: 4,
+                    height: 10
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn region_tiles_stay_in_region() {
+        let region = Tile {
+            x: 10,
+            y: 5,
+            width: 20,
+            height: 12,
+        };
+        let tiles = tiles(&region, 8, TileOrder::Spiral);
+        assert_eq!(tiles.iter().map(Tile::area).sum::<u32>(), region.area());
+        assert!(tiles
+            .iter()
+            .all(|tile| tile.intersection(&region) == Some(*tile)));
+    }
+
+    #[test]
+    fn intersections() {
+        let a = image(10, 10);
+        let b = Tile {
+            x: 5,
+            y: 8,
+            width: 10,
+            height: 10,
+        };
+        assert_eq!(
+            a.intersection(&b),
+            Some(Tile {
+                x: 5,
+                y: 8,
+                width: 5,
+                height: 2
+            })
+        );
+        assert!(b.contains(14, 17));
+        assert!(!b.contains(15, 8));
+        assert_eq!(a.intersection(&Tile { x: 10, ..b }), None);
+
+        let huge = Tile {
+            x: u32::MAX - 1,
+            y: 0,
+            width: 10,
+            height: 10,
+        };
+        assert!(huge.contains(u32::MAX - 1, 0));
+        assert_eq!(a.intersection(&huge), None);
+    }
+
+    #[test]
+    fn spiral_starts_in_the_center() {
+        let tiles = tiles(&image(48, 48), 16, TileOrder::Spiral);
+        assert_eq!((tiles[0].x, tiles[0].y), (16, 16));
+    }
+
+    #[test]
+    fn hilbert_steps_between_neighbors() {
+        let tiles = tiles(&image(64, 64), 16, TileOrder::Hilbert);
+        for pair in tiles.windows(2) {
+            let dx = (pair[0].x as i64 - pair[1].x as i64).abs();
+            let dy = (pair[0].y as i64 - pair[1].y as i64).abs();
+            assert_eq!(dx + dy, 16);
+        }
+    }
+
+    #[test]
+    fn hilbert_mostly_steps_between_neighbors_on_other_grids() {
+        // a 5x3 grid of tiles, sorted along the curve through the 8x8 grid around it
+        let tiles = tiles(&image(70, 45), 16, TileOrder::Hilbert);
+        let steps = tiles.windows(2).map(|pair| {
+            let dx = (pair[0].x as i64 - pair[1].x as i64).abs();
+            let dy = (pair[0].y as i64 - pair[1].y as i64).abs();
+            dx + dy
+        });
+        let (neighbors, jumps): (Vec<i64>, Vec<i64>) = steps.partition(|&step| step == 16);
+        assert!(!jumps.is_empty());
+        assert!(neighbors.len() > 2 * jumps.len());
+    }
+
+    #[test]
+    fn spiral_over_a_strip_only_visits_the_strip() {
+        let cells = spiral(1, 100_000);
+        assert_eq!(cells.len(), 100_000);
+        assert_eq!(cells[0], (0, 49_999));
+        assert_eq!(cells[1..3], [(0, 50_000), (0, 49_998)]);
+    }
+}
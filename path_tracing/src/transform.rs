@@ -0,0 +1,420 @@
+use super::{cross, mix, normalize, Aabb, Mat3, Mat4, Normal3, Point3, Quat, Vec3};
+use std::ops::Mul;
+
+/// An affine transform placing objects in the scene, kept together with its inverse
+/// so rays can be taken into object space without inverting at render time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Mat4,
+    inverse: Mat4,
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Mat4::identity(),
+            inverse: Mat4::identity(),
+        }
+    }
+
+    /// Returns None if the matrix can't be inverted or isn't affine.
+    pub fn from_matrix(matrix: Mat4) -> Option<Transform> {
+        if !matrix.is_affine() {
+            return None;
+        }
+        let inverse = matrix.inverse()?;
+        Some(Transform { matrix, inverse })
+    }
+
+    pub fn translation(offset: Vec3) -> Transform {
+        Transform {
+            matrix: Mat4::from_linear(&Mat3::identity(), offset),
+            inverse: Mat4::from_linear(&Mat3::identity(), -offset),
+        }
+    }
+
+    /// Scales along each axis.
+    /// Returns None if a factor is zero or not finite, since that can't be undone.
+    pub fn scaling(factors: Vec3) -> Option<Transform> {
+        if !is_invertible_scale(factors) {
+            return None;
+        }
+        let &[x, y, z] = &factors.0;
+        let origin = Vec3::fill(0.0);
+        Some(Transform {
+            matrix: Mat4::from_linear(&Mat3::diagonal(factors), origin),
+            inverse: Mat4::from_linear(
+                &Mat3::diagonal(Vec3::new(1.0 / x, 1.0 / y, 1.0 / z)),
+                origin,
+            ),
+        })
+    }
+
+    /// Rotates counter-clockwise by the given number of degrees about an axis through the origin.
+    pub fn rotation(axis: Vec3, degrees: f64) -> Transform {
+        let rotation = Quat::from_axis_angle(axis, degrees).normalize().to_mat3();
+        let origin = Vec3::fill(0.0);
+        // rotations are orthogonal, so their inverse is the transpose
+        Transform {
+            matrix: Mat4::from_linear(&rotation, origin),
+            inverse: Mat4::from_linear(&rotation.transpose(), origin),
+        }
+    }
+
+    /// Scales, then rotates, then translates.
+    /// Returns None if a scale factor is zero or not finite, since that can't be undone.
+    pub fn from_trs(translation: Vec3, rotation: Quat, scale: Vec3) -> Option<Transform> {
+        if !is_invertible_scale(scale) {
+            return None;
+        }
+        let rotation = rotation.normalize().to_mat3();
+        let &[x, y, z] = &scale.0;
+        let inverse = Mat3::diagonal(Vec3::new(1.0 / x, 1.0 / y, 1.0 / z)) * rotation.transpose();
+        Some(Transform {
+            matrix: Mat4::from_linear(&(rotation * Mat3::diagonal(scale)), translation),
+            inverse: Mat4::from_linear(&inverse, -(inverse * translation)),
+        })
+    }
+
+    /// Places an object at eye facing target, with its y axis towards up.
+    /// Like the camera, the object looks down its own -z axis.
+    pub fn look_at(eye: Point3, target: Point3, up: Vec3) -> Transform {
+        let w = normalize(eye - target);
+        let u = normalize(cross(up, w));
+        let v = cross(w, u);
+        let rotation = Mat3::from_columns(u, v, w);
+        let inverse = rotation.transpose();
+        Transform {
+            matrix: Mat4::from_linear(&rotation, eye.0),
+            inverse: Mat4::from_linear(&inverse, -(inverse * eye.0)),
+        }
+    }
+
+    /// Splits the transform into translation, rotation and scale, such that
+    /// from_trs gives it back. Returns None if it shears, which those can't describe,
+    /// or if it is singular. A reflection comes back as a negative x scale.
+    pub fn decompose(&self) -> Option<(Vec3, Quat, Vec3)> {
+        let linear = self.matrix.linear();
+        let mut scale = Vec3::new(
+            linear.column(0).length(),
+            linear.column(1).length(),
+            linear.column(2).length(),
+        );
+        if !is_invertible_scale(scale) || linear.determinant() == 0.0 {
+            return None;
+        }
+        if linear.determinant() < 0.0 {
+            scale.0[0] = -scale.0[0];
+        }
+        let rotation = Mat3::from_columns(
+            linear.column(0) / scale.x(),
+            linear.column(1) / scale.y(),
+            linear.column(2) / scale.z(),
+        );
+        let error = rotation.transpose() * rotation - Mat3::identity();
+        if error.0.iter().flatten().any(|e| e.abs() > 1e-9) {
+            return None;
+        }
+        Some((self.matrix.translation(), Quat::from_mat3(&rotation), scale))
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point3) -> Point3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Transforms a surface normal, which takes the inverse transpose so normals stay
+    /// perpendicular to surfaces that are scaled unevenly. The result is not normalized.
+    pub fn normal(&self, n: Normal3) -> Normal3 {
+        Normal3(self.inverse.linear().transpose() * n.0)
+    }
+
+    /// A box around the transformed box. It can be looser than the box itself after a rotation.
+    pub fn bounds(&self, b: &Aabb) -> Aabb {
+        let corners = b.corners().map(|corner| self.point(corner));
+        Aabb::around(corners.to_vec()).expect("a box has corners")
+    }
+}
+
+/// Composes transforms: (a * b) applies b first, then a.
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Transform {
+        Transform {
+            matrix: self.matrix * rhs.matrix,
+            inverse: rhs.inverse * self.inverse,
+        }
+    }
+}
+
+/// A transform that changes while the shutter is open. Translation and scale move in a
+/// straight line and the rotation turns at a steady rate from start at time0 to end at time1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimatedTransform {
+    start: (Vec3, Quat, Vec3),
+    end: (Vec3, Quat, Vec3),
+    time0: f64,
+    time1: f64,
+}
+
+impl AnimatedTransform {
+    /// Returns None if either end shears, since shears can't be interpolated this way,
+    /// or if only one end is a reflection, since the scale would pass through zero.
+    pub fn new(
+        start: Transform,
+        end: Transform,
+        time0: f64,
+        time1: f64,
+    ) -> Option<AnimatedTransform> {
+        let (start, end) = (start.decompose()?, end.decompose()?);
+        if start.2.x().signum() != end.2.x().signum() {
+            return None;
+        }
+        Some(AnimatedTransform {
+            start,
+            end,
+            time0,
+            time1,
+        })
+    }
+
+    /// The transform at the given time, held at the ends outside [time0, time1].
+    pub fn at(&self, time: f64) -> Transform {
+        let t = if self.time1 > self.time0 {
+            ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (t0, r0, s0) = self.start;
+        let (t1, r1, s1) = self.end;
+        Transform::from_trs(mix(t0, t1, t), r0.slerp(&r1, t), mix(s0, s1, t))
+            .expect("scales between two of the same sign are never zero")
+    }
+
+    /// A box around everything the transformed box sweeps through over the whole motion.
+    pub fn bounds(&self, b: &Aabb) -> Aabb {
+        let (start, end) = (self.at(self.time0), self.at(self.time1));
+        let swept = start.bounds(b).union(&end.bounds(b));
+        if self.start.1 == self.end.1 {
+            // without rotation every point moves in a straight line, so the ends are enough
+            return swept;
+        }
+        // otherwise bound the rotation by the furthest any corner can get from the pivot
+        let reach = b
+            .corners()
+            .iter()
+            .flat_map(|c| [self.start.2 * c.0, self.end.2 * c.0])
+            .map(|v| v.length())
+            .fold(0.0, f64::max);
+        let path = Aabb::new(Point3(self.start.0), Point3(self.end.0));
+        path.expand(reach)
+    }
+}
+
+/// Whether every scale factor can be divided by.
+fn is_invertible_scale(scale: Vec3) -> bool {
+    scale.0.iter().all(|s| s.is_finite() && *s != 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::dot;
+    use super::*;
+
+    fn assert_near<T: Into<Vec3>>(a: T, b: T) {
+        let (a, b) = (a.into(), b.into());
+        assert!((a - b).length() < 1e-12, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn animated_transforms_interpolate() {
+        let start = Transform::translation(Vec3::new(0.0, 0.0, 0.0));
+        let end = Transform::translation(Vec3::new(2.0, 0.0, 0.0))
+            * Transform::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0)
+            * Transform::scaling(Vec3::fill(3.0)).unwrap();
+        let animated = AnimatedTransform::new(start, end, 0.0, 1.0).unwrap();
+        assert_near(
+            animated.at(0.5).point(Point3::new(1.0, 0.0, 0.0)),
+            Point3::new(1.0 + 2.0 * f64::sqrt(0.5), 0.0, -2.0 * f64::sqrt(0.5)),
+        );
+        assert_eq!(animated.at(-1.0), animated.at(0.0));
+        assert_near(
+            animated.at(2.0).point(Point3::new(1.0, 0.0, 0.0)),
+            end.point(Point3::new(1.0, 0.0, 0.0)),
+        );
+
+        let unit = Aabb::new(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+        let bounds = animated.bounds(&unit);
+        for i in 0..=10 {
+            let moved = animated.at(i as f64 / 10.0).bounds(&unit);
+            assert!(bounds.contains(moved.min) && bounds.contains(moved.max));
+        }
+        let shear = Mat4([
+            [1.0, 1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let shear = Transform::from_matrix(shear).unwrap();
+        assert!(AnimatedTransform::new(start, shear, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn points_and_vectors() {
+        let move_up = Transform::translation(Vec3::new(0.0, 2.0, 0.0));
+        assert_eq!(
+            move_up.point(Point3::new(1.0, 1.0, 1.0)),
+            Point3::new(1.0, 3.0, 1.0)
+        );
+        assert_eq!(move_up.vector(Vec3::fill(1.0)), Vec3::fill(1.0));
+
+        let quarter_turn = Transform::rotation(Vec3::new(0.0, 1.0, 0.0), 90.0);
+        assert_near(
+            quarter_turn.point(Point3::new(1.0, 0.0, 0.0)),
+            Point3::new(0.0, 0.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn composition_and_inverse() {
+        let transform = Transform::translation(Vec3::new(1.0, 2.0, 3.0))
+            * Transform::rotation(Vec3::new(1.0, 1.0, 0.0), 30.0)
+            * Transform::scaling(Vec3::new(2.0, 0.5, 4.0)).unwrap();
+        let p = Point3::new(0.3, -1.2, 2.5);
+        assert_near(transform.inverse().point(transform.point(p)), p);
+        assert_near((transform * transform.inverse()).point(p), p);
+        // scaling happens before the translation
+        let scale_then_move = Transform::translation(Vec3::new(1.0, 0.0, 0.0))
+            * Transform::scaling(Vec3::fill(2.0)).unwrap();
+        assert_eq!(
+            scale_then_move.point(Point3::new(1.0, 0.0, 0.0)),
+            Point3::new(3.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn normals_stay_perpendicular() {
+        let squash = Transform::scaling(Vec3::new(1.0, 0.25, 1.0)).unwrap();
+        // a surface sloping at 45 degrees
+        let tangent = Vec3::new(1.0, 1.0, 0.0);
+        let normal = Normal3::new(1.0, -1.0, 0.0);
+        assert!(dot(squash.vector(tangent), squash.normal(normal)).abs() < 1e-12);
+        // transforming it like a direction would tilt it off the surface
+        assert!(dot(squash.vector(tangent), squash.vector(normal.0)).abs() > 0.1);
+    }
+
+    #[test]
+    fn general_matrices() {
+        let matrix = Transform::rotation(Vec3::new(1.0, 0.0, 1.0), 60.0).matrix
+            * Mat4::from_linear(
+                &Mat3([[1.0, 0.5, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 2.0]]),
+                Vec3::new(4.0, 5.0, 6.0),
+            );
+        let transform = Transform::from_matrix(matrix).unwrap();
+        let p = Point3::new(-1.0, 2.0, 0.5);
+        assert_near(transform.inverse().point(transform.point(p)), p);
+
+        let mut flat = Mat4::identity();
+        flat.0[1][1] = 0.0;
+        assert!(Transform::from_matrix(flat).is_none());
+        let mut projective = Mat4::identity();
+        projective.0[3][2] = 1.0;
+        assert!(Transform::from_matrix(projective).is_none());
+    }
+
+    #[test]
+    fn look_at_faces_the_target() {
+        let eye = Point3::new(1.0, 2.0, 3.0);
+        let target = Point3::new(4.0, 2.0, -1.0);
+        let transform = Transform::look_at(eye, target, Vec3::new(0.0, 1.0, 0.0));
+        assert_near(transform.point(Point3::origin()), eye);
+        // the target lies straight down -z, at its distance of 5
+        assert_near(
+            transform.inverse().point(target),
+            Point3::new(0.0, 0.0, -5.0),
+        );
+        assert_near(
+            transform.vector(Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn decomposition_round_trip() {
+        let translation = Vec3::new(1.0, -2.0, 3.0);
+        let rotation = Quat::from_axis_angle(Vec3::new(1.0, 2.0, -1.0), 135.0);
+        let scale = Vec3::new(2.0, 0.5, 3.0);
+        let transform = Transform::from_trs(translation, rotation, scale).unwrap();
+        let (t, r, s) = transform.decompose().unwrap();
+        assert_near(t, translation);
+        assert_near(s, scale);
+        assert!((r.dot(&rotation).abs() - 1.0).abs() < 1e-12);
+
+        let p = Point3::new(0.7, 0.1, -0.4);
+        assert_near(
+            Transform::from_trs(t, r, s).unwrap().point(p),
+            (Transform::translation(translation)
+                * Transform::rotation(Vec3::new(1.0, 2.0, -1.0), 135.0)
+                * Transform::scaling(scale).unwrap())
+            .point(p),
+        );
+
+        let mirror = Transform::scaling(Vec3::new(1.0, -1.0, 1.0)).unwrap();
+        let (_, r, s) = mirror.decompose().unwrap();
+        assert_near(
+            Transform::from_trs(Vec3::fill(0.0), r, s).unwrap().point(p),
+            mirror.point(p),
+        );
+        assert!(s.x() < 0.0);
+
+        let shear = Transform::from_matrix(Mat4::from_linear(
+            &Mat3([[1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]),
+            Vec3::fill(0.0),
+        ))
+        .unwrap();
+        assert!(shear.decompose().is_none());
+    }
+
+    #[test]
+    fn scales_that_cant_be_undone_are_rejected() {
+        for bad in &[0.0, f64::INFINITY, f64::NAN] {
+            let scale = Vec3::new(1.0, *bad, 1.0);
+            assert!(Transform::scaling(scale).is_none());
+            assert!(Transform::from_trs(Vec3::fill(0.0), Quat::identity(), scale).is_none());
+        }
+
+        let flatten = Mat4::from_linear(
+            &Mat3([[1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 0.0, 1.0]]),
+            Vec3::fill(0.0),
+        );
+        let flat = Transform {
+            matrix: flatten,
+            inverse: flatten,
+        };
+        assert!(flat.decompose().is_none());
+
+        let mirror = Transform::scaling(Vec3::new(-1.0, 1.0, 1.0)).unwrap();
+        assert!(AnimatedTransform::new(Transform::identity(), mirror, 0.0, 1.0).is_none());
+    }
+}
@@ -1,7 +1,9 @@
 use super::vector::*;
 use rand::distributions::{Distribution, UnitSphereSurface};
 use rand::prelude::*;
+use rand::rngs::SmallRng;
 use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::ops::{Add, Mul, Sub};
 
 /// mix performs a linear interpolation between two like values
@@ -12,22 +14,26 @@ where
     a + (b - a) * t
 }
 
+thread_local! {
+    static RNG: RefCell<SmallRng> = RefCell::new(SmallRng::from_entropy());
+}
+
+/// Reseeds the random number generator used by the sampling functions on this thread.
+/// Renders seed it before each pixel so their results are reproducible.
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = SmallRng::seed_from_u64(seed));
+}
+
 /// returns a random point on the surface of a unit sphere
 pub fn random_in_unit_sphere() -> Vec3 {
-    let mut rng = rand::thread_rng();
     let sphere = UnitSphereSurface::new();
-    Vec3(sphere.sample(&mut rng))
+    RNG.with(|rng| Vec3(sphere.sample(&mut *rng.borrow_mut())))
 }
 
 /// returns a random point within a unit disk
 pub fn random_in_unit_disk() -> [f64; 2] {
-    let mut rng = rand::thread_rng();
     loop {
-        let p = Vec3::new(
-            2.0 * rng.gen::<f64>() - 1.0,
-            2.0 * rng.gen::<f64>() - 1.0,
-            0.0,
-        );
+        let p = Vec3::new(2.0 * rand() - 1.0, 2.0 * rand() - 1.0, 0.0);
         if p.length_squared() < 1.0 {
             return [p.0[0], p.0[1]];
         }
@@ -35,8 +41,7 @@ pub fn random_in_unit_disk() -> [f64; 2] {
 }
 
 pub fn rand() -> f64 {
-    let mut rng = rand::thread_rng();
-    rng.gen::<f64>()
+    RNG.with(|rng| rng.borrow_mut().gen::<f64>())
 }
 
 /// Returns a unit vector with the same direction as the input vector.
@@ -93,7 +98,7 @@ where
     let vector = vector.borrow();
     let normal = normal.borrow();
     let vector = normalize(vector);
-    let dt = dot(&vector, normal);
+    let dt = dot(vector, normal);
     let discriminant = 1.0 - ni_over_nt * ni_over_nt * (1.0 - dt * dt);
 
     if discriminant > 0.0 {
@@ -139,6 +144,15 @@ mod tests {
         assert_relative_eq!(random_in_unit_sphere().length_squared(), 1.0);
     }
 
+    #[test]
+    fn seeded_random() {
+        seed_random(7);
+        let a = (rand(), random_in_unit_sphere(), random_in_unit_disk());
+        seed_random(7);
+        let b = (rand(), random_in_unit_sphere(), random_in_unit_disk());
+        assert_eq!(a, b);
+    }
+
     #[test]
     fn trigonometry_functions() {
         assert_relative_eq!(f64::sin(std::f64::consts::PI), 0.0);
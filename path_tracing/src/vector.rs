@@ -13,15 +13,6 @@ impl Vec3 {
     pub fn fill(v: f64) -> Vec3 {
         Vec3([v, v, v])
     }
-    pub fn r(&self) -> f64 {
-        self.0[0]
-    }
-    pub fn g(&self) -> f64 {
-        self.0[1]
-    }
-    pub fn b(&self) -> f64 {
-        self.0[2]
-    }
     pub fn x(&self) -> f64 {
         self.0[0]
     }
@@ -1,13 +1,17 @@
 use super::hitable::*;
-use super::Ray;
+use super::{Aabb, Light, Ray};
 
-pub struct World(Vec<Box<dyn Hitable + Sync>>);
+/// The objects in a scene, and the lights shining on them.
+pub struct World {
+    objects: Vec<Box<dyn Hitable + Sync>>,
+    lights: Vec<Box<dyn Light>>,
+}
 
 impl Hitable for World {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord<'_>> {
         let mut closest = t_max;
-        let mut found: Option<HitRecord> = None;
-        for hitable in &self.0 {
+        let mut found: Option<HitRecord<'_>> = None;
+        for hitable in &self.objects {
             if let Some(hit) = hitable.hit(ray, t_min, closest) {
                 closest = hit.t;
                 found = Some(hit);
@@ -15,14 +19,37 @@ impl Hitable for World {
         }
         found
     }
+
+    fn bounding_box(&self, time0: f64, time1: f64) -> Option<Aabb> {
+        let mut boxes = self.objects.iter().map(|h| h.bounding_box(time0, time1));
+        let first = boxes.next()??;
+        boxes.try_fold(first, |bounds, b| Some(bounds.union(&b?)))
+    }
+}
+
+impl Default for World {
+    fn default() -> World {
+        World::new()
+    }
 }
 
 impl World {
     pub fn new() -> World {
-        World(vec![])
+        World {
+            objects: vec![],
+            lights: vec![],
+        }
     }
 
     pub fn push(&mut self, item: Box<dyn Hitable + Sync>) {
-        self.0.push(item);
+        self.objects.push(item);
+    }
+
+    pub fn add_light(&mut self, light: Box<dyn Light>) {
+        self.lights.push(light);
+    }
+
+    pub fn lights(&self) -> &[Box<dyn Light>] {
+        &self.lights
     }
 }